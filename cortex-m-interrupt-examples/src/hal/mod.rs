@@ -1,7 +1,6 @@
 use core::task::Waker;
 
-pub mod ssq;
-use ssq::{Consumer, Producer, SingleSlotQueue};
+use cortex_m_interrupt::{Consumer, Producer, SingleSlotQueue};
 
 #[cfg(any(
     feature = "stm32f1xx-hal",
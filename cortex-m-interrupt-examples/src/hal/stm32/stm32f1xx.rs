@@ -1,6 +1,8 @@
 use core::{
     future::Future,
+    mem::MaybeUninit,
     pin::Pin,
+    ptr::addr_of_mut,
     task::{self, Poll},
 };
 
@@ -8,7 +10,7 @@ use crate::hal::{WakerProducer, WakerQueue};
 
 use crate::hal::WakerConsumer;
 
-use cortex_m_interrupt::NvicInterruptRegistration;
+use cortex_m_interrupt::{NvicInterruptRegistration, NvicOccupation};
 
 use stm32f1xx_hal::{
     flash::FlashExt,
@@ -34,9 +36,29 @@ pub fn hw_setup() -> InputPin<CRL, 'C', 1> {
     pin
 }
 
+/// Whether EXTI line `line` has a pending interrupt that is not masked.
+///
+/// This can be used as the predicate of a line of `EXTI9_5` or `EXTI15_10` in
+/// [`cortex_m_interrupt::split_interrupt`].
+pub fn exti_fired(line: u8) -> bool {
+    let exti = unsafe { &*EXTI::ptr() };
+
+    exti.pr.read().bits() & exti.imr.read().bits() & (1 << line) != 0
+}
+
+/// The waker of every EXTI line.
+///
+/// This storage is indexed by line, as a `static` inside of the generic `AsyncExtiPin::new`
+/// would be shared by all pins, which would overwrite each other's waker.
+static mut WAKERS: [MaybeUninit<WakerConsumer<'static>>; 16] = {
+    const UNINIT: MaybeUninit<WakerConsumer<'static>> = MaybeUninit::uninit();
+    [UNINIT; 16]
+};
+
 pub struct AsyncExtiPin<CR, const P: char, const N: u8> {
     send_waker: WakerProducer<'static>,
     irq: InputPin<CR, P, N>,
+    occupation: NvicOccupation<Interrupt>,
 }
 
 impl<CR, const P: char, const N: u8> AsyncExtiPin<CR, P, N> {
@@ -45,9 +67,6 @@ impl<CR, const P: char, const N: u8> AsyncExtiPin<CR, P, N> {
         registration: T,
         irq: InputPin<CR, P, N>,
     ) -> AsyncExtiPin<CR, P, N> {
-        use core::mem::MaybeUninit;
-        static mut WAKER: MaybeUninit<WakerConsumer<'static>> = MaybeUninit::uninit();
-
         macro_rules! num_int_map {
             ($($start:literal..=$end:literal => $int:ident$(,)?)*) => {
                 match N {
@@ -71,10 +90,12 @@ impl<CR, const P: char, const N: u8> AsyncExtiPin<CR, P, N> {
 
         let (r, w) = waker_queue.split();
 
-        unsafe { WAKER = MaybeUninit::new(r) };
+        // NOTE(unsafe): the occupation of line `N` is not active yet, and is the only other place
+        // where the waker of line `N` is accessed.
+        unsafe { *addr_of_mut!(WAKERS[N as usize]) = MaybeUninit::new(r) };
 
-        registration.occupy(|| {
-            cortex_m::interrupt::free(|_| {
+        let occupation = registration.occupy_nvic(|| {
+            critical_section::with(|_| {
                 let exti = unsafe { &*EXTI::ptr() };
 
                 // Disable interrupt
@@ -82,12 +103,19 @@ impl<CR, const P: char, const N: u8> AsyncExtiPin<CR, P, N> {
                     .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << N)) });
             });
 
-            if let Some(waker) = unsafe { WAKER.assume_init_mut().dequeue() } {
+            // NOTE(unsafe): the waker of line `N` was initialized before this occupation was
+            // occupied, and no other line accesses it.
+            let waker = unsafe { (*addr_of_mut!(WAKERS[N as usize])).assume_init_mut() };
+            if let Some(waker) = waker.dequeue() {
                 waker.wake();
             }
         });
 
-        Self { send_waker: w, irq }
+        Self {
+            send_waker: w,
+            irq,
+            occupation,
+        }
     }
 }
 
@@ -107,7 +135,8 @@ where
         } else {
             self.send_waker.enqueue(cx.waker().clone());
 
-            cortex_m::interrupt::free(|_| {
+            // The occupation is the only other place where `imr` is modified.
+            self.occupation.masked(|| {
                 // Enable the interrupt
                 exti.imr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << N) });
             });
@@ -1,7 +1,7 @@
 use proc_macro_error::proc_macro_error;
 
 mod take;
-use take::Take;
+use take::{Cores, Take};
 
 mod take_exception;
 
@@ -9,6 +9,9 @@ mod take_nvic_interrupt;
 use take_exception::TakeException;
 use take_nvic_interrupt::TakeNvicInterrupt;
 
+mod occupy;
+use occupy::Occupy;
+
 #[proc_macro]
 #[proc_macro_error]
 pub fn take_nvic_interrupt(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -20,3 +23,14 @@ pub fn take_nvic_interrupt(input: proc_macro::TokenStream) -> proc_macro::TokenS
 pub fn take_exception(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     syn::parse_macro_input!(input as TakeException).build()
 }
+
+#[proc_macro_attribute]
+#[proc_macro_error]
+pub fn occupy(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let take = syn::parse_macro_input!(args as TakeNvicInterrupt);
+    let function = syn::parse_macro_input!(input as syn::ItemFn);
+    Occupy::new(take, function).build()
+}
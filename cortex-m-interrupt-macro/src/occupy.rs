@@ -0,0 +1,74 @@
+use proc_macro2::Span;
+use quote::quote;
+use syn::{ItemFn, ReturnType};
+
+use crate::TakeNvicInterrupt;
+
+pub struct Occupy {
+    take: TakeNvicInterrupt,
+    function: ItemFn,
+}
+
+impl Occupy {
+    pub fn new(take: TakeNvicInterrupt, function: ItemFn) -> Self {
+        Self { take, function }
+    }
+
+    pub fn build(&self) -> proc_macro::TokenStream {
+        let Self { take, function } = self;
+
+        if take.is_non_secure() {
+            proc_macro_error::abort!(
+                Span::call_site(),
+                "`target = non_secure` can not be used with `#[occupy]`, as the interrupt is occupied by the Non-secure image."
+            );
+        }
+
+        if take.is_per_core() {
+            proc_macro_error::abort!(
+                Span::call_site(),
+                "`cores` can not be used with `#[occupy]`, as it creates a registration per core.";
+                help = "Use `core = N` to occupy the interrupt on a single core."
+            );
+        }
+
+        let ItemFn {
+            attrs,
+            vis,
+            sig,
+            block,
+        } = function;
+
+        if !sig.inputs.is_empty()
+            || !sig.generics.params.is_empty()
+            || sig.asyncness.is_some()
+            || sig.unsafety.is_some()
+            || sig.abi.is_some()
+            || sig.variadic.is_some()
+            || !matches!(sig.output, ReturnType::Default)
+        {
+            proc_macro_error::abort!(
+                sig,
+                "An occupation must have the signature `fn()`.";
+                help = "Remove the arguments, return type and qualifiers of `{}`.", sig.ident
+            );
+        }
+
+        let ident = &sig.ident;
+        let interrupt_type = take.interrupt_type();
+        let registration = proc_macro2::TokenStream::from(take.build(true));
+
+        quote! {
+            #(#attrs)*
+            #vis fn #ident() -> ::cortex_m_interrupt::NvicOccupation<#interrupt_type> {
+                use ::cortex_m_interrupt::NvicInterruptRegistration;
+
+                fn #ident() #block
+
+                let registration = #registration;
+                registration.occupy_nvic(#ident)
+            }
+        }
+        .into()
+    }
+}
@@ -1,7 +1,24 @@
-use syn::{parse::Parse, Error, Ident, LitStr};
+use proc_macro2::TokenStream;
+use syn::{parse::Parse, Error, Ident, LitInt, LitStr};
+
+/// The cores that a taken interrupt can be occupied on.
+pub enum Cores {
+    /// Any core.
+    Any,
+    /// Only the given core.
+    One(LitInt),
+    /// Each of the given amount of cores, with an occupation per core.
+    Each(LitInt),
+}
 
 pub struct Take {
     irq: Ident,
+    /// The argument that is passed to occupations that take one.
+    argument: TokenStream,
+    /// The type of `argument`.
+    argument_type: TokenStream,
+    cores: Cores,
+    ram: bool,
 }
 
 impl Parse for Take {
@@ -13,50 +30,199 @@ impl Parse for Take {
             )
         })?;
 
-        Ok(Self { irq })
+        Ok(Self {
+            irq,
+            argument: quote::quote! { () },
+            argument_type: quote::quote! { () },
+            cores: Cores::Any,
+            ram: false,
+        })
     }
 }
 
 impl Take {
-    pub fn new(irq: Ident) -> Self {
-        Self { irq }
+    pub fn new(
+        irq: Ident,
+        argument: TokenStream,
+        argument_type: TokenStream,
+        cores: Cores,
+        ram: bool,
+    ) -> Self {
+        Self {
+            irq,
+            argument,
+            argument_type,
+            cores,
+            ram,
+        }
+    }
+
+    /// The generic parameters of `Handle`, and of the registrations built on top of it.
+    ///
+    /// With `Cores::Each`, these are `<const CORE: u8>`.
+    pub fn generics(&self) -> TokenStream {
+        if let Cores::Each(_) = self.cores {
+            quote::quote! { <const CORE: u8> }
+        } else {
+            quote::quote! {}
+        }
+    }
+
+    /// The generic arguments that go with `generics`.
+    pub fn generic_arguments(&self) -> TokenStream {
+        if let Cores::Each(_) = self.cores {
+            quote::quote! { <CORE> }
+        } else {
+            quote::quote! {}
+        }
+    }
+
+    /// The value of `NvicInterruptRegistration::CORE`.
+    pub fn core(&self) -> TokenStream {
+        match &self.cores {
+            Cores::Any => quote::quote! { None },
+            Cores::One(core) => quote::quote! { Some(#core) },
+            Cores::Each(_) => quote::quote! { Some(CORE) },
+        }
+    }
+
+    /// Generate a check that panics if the current core is not the
+    /// core that this registration is taken for.
+    pub fn assert_core(&self) -> proc_macro2::TokenStream {
+        let Take { irq, cores, .. } = self;
+
+        let core = match cores {
+            Cores::Any => return quote::quote! {},
+            Cores::One(core) => quote::quote! { #core },
+            Cores::Each(_) => quote::quote! { CORE },
+        };
+
+        quote::quote! {
+            let current_core = ::cortex_m_interrupt::core_id();
+            if current_core != #core {
+                panic!(
+                    concat!("Attempted to occupy interrupt ", stringify!(#irq), " for core {} from core {}"),
+                    #core,
+                    current_core
+                );
+            }
+        }
     }
 
+    /// Generate the items that take the interrupt: the exported interrupt handler, and `Handle`,
+    /// which implements `InterruptRegistration` and `OccupyWith<argument_type>` by publishing the
+    /// occupation to that handler.
     pub fn build(&self) -> proc_macro2::TokenStream {
-        let Take { irq } = self;
+        let Take {
+            irq,
+            argument,
+            argument_type,
+            cores,
+            ram,
+        } = self;
 
         let interrupt_export_name = LitStr::new(&irq.to_string(), irq.span());
 
-        quote::quote! {
-            {
-                struct Handle;
+        // The trampoline is placed in `.data`, which is copied to RAM on startup.
+        let link_section = if *ram {
+            let section = LitStr::new(&format!(".data.cortex_m_interrupt.{}", irq), irq.span());
+            quote::quote! {
+                #[unsafe(link_section = #section)]
+                #[inline(never)]
+            }
+        } else {
+            quote::quote! {}
+        };
+
+        // `REGISTERED` is exported with a descriptive name, so that taking the same interrupt more
+        // than once in a program results in a duplicate symbol error that names the interrupt, and
+        // the crates that take it.
+        let registered_export_name = LitStr::new(
+            &format!("cortex-m-interrupt: `{}` is taken more than once", irq),
+            irq.span(),
+        );
+
+        let assert_core = self.assert_core();
+        let generics = self.generics();
+        let generic_arguments = self.generic_arguments();
+
+        // Every core that services the interrupt runs the trampoline. If the interrupt is not
+        // taken for the current core, the trampoline runs the default handler, as it does for an
+        // unoccupied interrupt.
+        let (registered_type, registered_new, registered, dispatch) = match cores {
+            Cores::Any => (
+                quote::quote! { ::cortex_m_interrupt::Handler },
+                quote::quote! { ::cortex_m_interrupt::Handler::new() },
+                quote::quote! { REGISTERED },
+                quote::quote! { let registered = &REGISTERED; },
+            ),
+            Cores::One(core) => (
+                quote::quote! { ::cortex_m_interrupt::Handler },
+                quote::quote! { ::cortex_m_interrupt::Handler::new() },
+                quote::quote! { REGISTERED },
+                quote::quote! {
+                    if ::cortex_m_interrupt::core_id() != #core {
+                        ::cortex_m_interrupt::DefaultHandler_();
+                    }
+                    let registered = &REGISTERED;
+                },
+            ),
+            Cores::Each(cores) => (
+                quote::quote! { [::cortex_m_interrupt::Handler; #cores] },
+                quote::quote! { [const { ::cortex_m_interrupt::Handler::new() }; #cores] },
+                quote::quote! { REGISTERED[CORE as usize] },
+                quote::quote! {
+                    let Some(registered) = REGISTERED.get(::cortex_m_interrupt::core_id() as usize) else {
+                        ::cortex_m_interrupt::DefaultHandler_();
+                    };
+                },
+            ),
+        };
 
-                static REGISTERED: ::cortex_m_interrupt::atomic_polyfill::AtomicBool = ::cortex_m_interrupt::atomic_polyfill::AtomicBool::new(false);
+        quote::quote! {
+            struct Handle #generics;
 
-                static mut HANDLER: fn() = || { unsafe { ::cortex_m_interrupt::DefaultHandler_()  } };
+            // The occupation is published through an atomic pointer, so that the interrupt
+            // can never observe a partially written occupation.
+            #[unsafe(export_name = #registered_export_name)]
+            static REGISTERED: #registered_type = #registered_new;
 
-                #[export_name = #interrupt_export_name]
+            // The handler is scoped, so that its name does not shadow an interrupt that is
+            // imported by name.
+            const _: () = {
+                #[unsafe(export_name = #interrupt_export_name)]
+                #link_section
                 #[allow(non_snake_case)]
                 pub unsafe extern "C" fn #irq() {
-                    (HANDLER)();
+                    #dispatch
+                    if !registered.run(#argument) {
+                        ::cortex_m_interrupt::DefaultHandler_();
+                    }
                 }
+            };
 
-               impl ::cortex_m_interrupt::InterruptRegistration for Handle {
-                    #[inline(always)]
-                    fn occupy(self, f: fn()) {
-                        if REGISTERED.swap(true, core::sync::atomic::Ordering::Acquire) {
-                            panic!(stringify!(Attempted to occupy already-occupied interrupt #irq))
-                        }
-
-                        unsafe {
-                            HANDLER = f;
-                        }
+            impl #generics ::cortex_m_interrupt::InterruptRegistration for Handle #generic_arguments {
+                #[inline(always)]
+                fn occupy(self, f: fn()) {
+                    #assert_core
 
-                        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::Release);
+                    if !#registered.occupy(f) {
+                        panic!(stringify!(Attempted to occupy already-occupied interrupt #irq))
                     }
                 }
+            }
+
+            impl #generics ::cortex_m_interrupt::OccupyWith<#argument_type> for Handle #generic_arguments {
+                #[inline(always)]
+                fn occupy_with(self, f: fn(#argument_type)) {
+                    #assert_core
 
-                Handle
+                    // NOTE(unsafe): the interrupt handler passes `#argument`, which is of the type
+                    // that `f` takes.
+                    if !unsafe { #registered.occupy_with(f) } {
+                        panic!(stringify!(Attempted to occupy already-occupied interrupt #irq))
+                    }
+                }
             }
         }
     }
@@ -15,7 +15,15 @@ impl Parse for TakeException {
 
 impl TakeException {
     pub fn build(&self) -> proc_macro::TokenStream {
-        let take = crate::Take::new(self.exception.clone()).build();
+        let exception = &self.exception;
+        let take = crate::Take::new(
+            exception.clone(),
+            quote! { ::cortex_m_interrupt::cortex_m::peripheral::scb::Exception::#exception },
+            quote! { ::cortex_m_interrupt::cortex_m::peripheral::scb::Exception },
+            crate::Cores::Any,
+            false,
+        )
+        .build();
 
         match self.exception.to_string().as_str() {
             "DefaultHandler" | "HardFault" => {
@@ -24,17 +32,22 @@ impl TakeException {
             _ => {}
         }
 
-        let exception = &self.exception;
-
         quote! {{
+            #take
+
             struct ExceptionRegistration {
                 exception: ::cortex_m_interrupt::cortex_m::peripheral::scb::Exception,
             }
 
             impl ::cortex_m_interrupt::InterruptRegistration for ExceptionRegistration {
                 fn occupy(self, f: fn()) {
-                    let registration = #take;
-                    registration.occupy(f);
+                    ::cortex_m_interrupt::InterruptRegistration::occupy(Handle, f);
+                }
+            }
+
+            impl ::cortex_m_interrupt::OccupyWith<::cortex_m_interrupt::cortex_m::peripheral::scb::Exception> for ExceptionRegistration {
+                fn occupy_with(self, f: fn(::cortex_m_interrupt::cortex_m::peripheral::scb::Exception)) {
+                    ::cortex_m_interrupt::OccupyWith::occupy_with(Handle, f);
                 }
             }
 
@@ -1,107 +1,549 @@
 use proc_macro_error::{abort, ResultExt};
 use quote::quote;
 use syn::{
-    parse::Parse,
+    parenthesized,
+    parse::{Parse, ParseStream},
     punctuated::Punctuated,
-    token::{Colon2, Comma},
-    LitInt, Path, PathSegment,
+    token::{Colon, Colon2, Comma, Eq, Paren},
+    Error, Expr, ExprLit, Ident, Lit, LitBool, LitInt, Path, PathSegment, Type, TypePath,
 };
 
+/// The security state that an interrupt is targeted at.
+enum Target {
+    Secure,
+    NonSecure,
+}
+
 pub struct TakeNvicInterrupt {
     interrupt_path: Path,
-    priority: LitInt,
+    interrupt_type: Option<Type>,
+    priority: Expr,
+    sub_priority: Option<LitInt>,
+    core: Option<LitInt>,
+    cores: Option<LitInt>,
+    target: Option<Target>,
+    prio_bits: Option<Expr>,
+    enable: Option<LitBool>,
+    clear_pending: Option<LitBool>,
+    ram: Option<LitBool>,
+}
+
+/// The options of `take_nvic_interrupt`, given as `key = value` arguments.
+#[derive(Default)]
+struct Options {
+    /// The keys that have been specified so far, used to detect duplicates.
+    keys: Vec<Ident>,
+    priority: Option<(Expr, Option<LitInt>)>,
+    core: Option<LitInt>,
+    cores: Option<LitInt>,
+    target: Option<Target>,
+    prio_bits: Option<Expr>,
+    enable: Option<LitBool>,
+    clear_pending: Option<LitBool>,
+    ram: Option<LitBool>,
+}
+
+impl Options {
+    /// Parse a single `key = value` option.
+    fn parse_option(&mut self, input: ParseStream) -> syn::Result<()> {
+        let key: Ident = input.parse()?;
+        let _ = input.parse::<Eq>()?;
+
+        if let Some(first) = self.keys.iter().find(|first| **first == key) {
+            abort!(
+                key,
+                "Duplicate option `{}`.", key;
+                note = first.span() => "`{}` was first specified here.", first
+            );
+        }
+
+        match key.to_string().as_str() {
+            "priority" => {
+                if self.priority.is_some() {
+                    abort!(
+                        key,
+                        "Duplicate option `priority`.";
+                        note = "The priority was already specified as the second argument."
+                    );
+                }
+                self.priority = Some(parse_priority(input)?);
+            }
+            "core" => {
+                let value: LitInt = input.parse()?;
+                value.base10_parse::<u8>()?;
+                self.core = Some(value);
+            }
+            "cores" => {
+                let value: LitInt = input.parse()?;
+                if value.base10_parse::<u8>()? == 0 {
+                    return Err(Error::new(value.span(), "Expected at least 1 core."));
+                }
+                self.cores = Some(value);
+            }
+            "target" => {
+                let value: Ident = input.parse()?;
+                self.target = Some(match value.to_string().as_str() {
+                    "secure" => Target::Secure,
+                    "non_secure" => Target::NonSecure,
+                    _ => {
+                        return Err(Error::new(
+                            value.span(),
+                            "Expected `secure` or `non_secure`.",
+                        ))
+                    }
+                });
+            }
+            "prio_bits" => self.prio_bits = Some(input.parse()?),
+            "enable" => self.enable = Some(input.parse()?),
+            "clear_pending" => self.clear_pending = Some(input.parse()?),
+            "ram" => self.ram = Some(input.parse()?),
+            _ => abort!(
+                key,
+                "Unknown option `{}`.", key;
+                help = "Expected one of `priority`, `core`, `cores`, `target`, `prio_bits`, `enable`, `clear_pending` or `ram`."
+            ),
+        }
+
+        self.keys.push(key);
+        Ok(())
+    }
+}
+
+/// Parse a priority, which is either `preempt`, or `(preempt, sub)`. A parenthesized
+/// expression without a sub-priority is a regular priority expression.
+fn parse_priority(input: ParseStream) -> syn::Result<(Expr, Option<LitInt>)> {
+    if input.peek(Paren) {
+        let content;
+        parenthesized!(content in input);
+        let priority = content.parse()?;
+
+        if content.is_empty() {
+            Ok((priority, None))
+        } else {
+            let _ = content.parse::<Comma>()?;
+            let sub_priority: LitInt = content.parse()?;
+            sub_priority.base10_parse::<u8>()?;
+            Ok((priority, Some(sub_priority)))
+        }
+    } else {
+        Ok((input.parse()?, None))
+    }
 }
 
 impl Parse for TakeNvicInterrupt {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let interrupt_path = input.parse()?;
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let interrupt_path: Path = input.parse()?;
+
+        // The type of the interrupt may be specified explicitly, as in `EXTI15_10: Interrupt`.
+        let interrupt_type = if input.parse::<Option<Colon>>()?.is_some() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
         let _ = input.parse::<Comma>()?;
-        let priority = input.parse()?;
+
+        let mut options = Options::default();
+
+        // The priority is either given positionally, or as the `priority` option.
+        if input.peek(Ident) && input.peek2(Eq) {
+            options.parse_option(input)?;
+        } else {
+            options.priority = Some(parse_priority(input)?);
+        }
+
+        while input.parse::<Option<Comma>>()?.is_some() && !input.is_empty() {
+            options.parse_option(input)?;
+        }
+
+        let (priority, sub_priority) = if let Some(priority) = options.priority {
+            priority
+        } else {
+            abort!(
+                interrupt_path,
+                "Missing priority.";
+                help = "Specify a priority with `priority = <priority>`."
+            )
+        };
+
+        if let (Some(core), Some(cores)) = (&options.core, &options.cores) {
+            abort!(
+                cores,
+                "`cores` can not be used together with `core`.";
+                note = core.span() => "`core` was specified here."
+            );
+        }
+
         Ok(Self {
             interrupt_path,
+            interrupt_type,
             priority,
+            sub_priority,
+            core: options.core,
+            cores: options.cores,
+            target: options.target,
+            prio_bits: options.prio_bits,
+            enable: options.enable,
+            clear_pending: options.clear_pending,
+            ram: options.ram,
         })
     }
 }
 
 impl TakeNvicInterrupt {
-    pub fn build(&self, use_logical_priority: bool) -> proc_macro::TokenStream {
-        let Self {
-            interrupt_path,
-            priority,
-        } = self;
+    /// Whether this interrupt is routed to the Non-secure state, in which case no
+    /// occupation is created.
+    pub fn is_non_secure(&self) -> bool {
+        matches!(self.target, Some(Target::NonSecure))
+    }
+
+    /// Whether this interrupt is taken for each of several cores, in which case a registration
+    /// per core is created.
+    pub fn is_per_core(&self) -> bool {
+        self.cores.is_some()
+    }
 
+    /// The type of the interrupt.
+    ///
+    /// Without an explicit type, this is the path to the enum variant without its last segment.
+    pub fn interrupt_type(&self) -> Type {
+        let interrupt_path = &self.interrupt_path;
         let int_path_len = interrupt_path.segments.len();
         let interrupt_ident = interrupt_path.segments.last().unwrap();
 
-        if int_path_len <= 1 {
+        if let Some(interrupt_type) = &self.interrupt_type {
+            interrupt_type.clone()
+        } else if int_path_len <= 1 {
             abort!(
                 interrupt_path,
                 "The interrupt must be specified with a least 2 path segments. For example: `Interrupt::{}`",
-                interrupt_ident.ident.to_string()
+                interrupt_ident.ident.to_string();
+                help = "Alternatively, specify the type of the interrupt: `{}: Interrupt`", interrupt_ident.ident.to_string()
             )
+        } else {
+            let segments = interrupt_path.segments.iter().take(int_path_len - 1);
+            let segments: Punctuated<PathSegment, Colon2> = segments.cloned().collect();
+
+            Type::Path(TypePath {
+                qself: None,
+                path: Path {
+                    leading_colon: interrupt_path.leading_colon,
+                    segments,
+                },
+            })
         }
+    }
 
-        let interrupt_type = self.interrupt_path.segments.iter().take(int_path_len - 1);
-        let interrupt_type: Punctuated<PathSegment, Colon2> =
-            interrupt_type.map(|s| s.clone()).collect();
+    pub fn build(&self, use_logical_priority: bool) -> proc_macro::TokenStream {
+        let Self {
+            interrupt_path,
+            priority,
+            sub_priority,
+            core,
+            cores,
+            target,
+            prio_bits,
+            enable,
+            clear_pending,
+            ram,
+            ..
+        } = self;
 
-        let prio_value: u32 = priority.base10_parse().unwrap_or_abort();
-        if prio_value == 0 {
-            abort!(priority, "Priority must be 1 or greater.");
-        }
+        let interrupt_ident = interrupt_path.segments.last().unwrap();
+        let interrupt_type = self.interrupt_type();
+
+        // A literal priority is checked by the macro itself, any other expression is
+        // checked when the `Priority` constant is evaluated.
+        let priority_value = if let Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) = priority
+        {
+            let prio_value: u32 = lit.base10_parse().unwrap_or_abort();
+            if prio_value == 0 {
+                abort!(priority, "Priority must be 1 or greater.");
+            }
+            quote! { ::cortex_m_interrupt::Priority::new(#lit) }
+        } else {
+            quote! { ::cortex_m_interrupt::PriorityValue(#priority).priority() }
+        };
+
+        let define_priority = quote! {
+            const __PRIORITY: ::cortex_m_interrupt::Priority = #priority_value;
+        };
+
+        let sub_priority = if let Some(sub_priority) = sub_priority {
+            quote! { #sub_priority }
+        } else {
+            quote! { 0 }
+        };
+
+        // With the amount of priority bits provided, the range of the priority is checked at
+        // compile time, and the priority bits do not have to be probed at runtime.
+        //
+        // The split into preemption priority and sub-priority bits depends on the priority
+        // grouping, which is only known at runtime. The logical priority `p` needs
+        // `log2(p.next_power_of_two())` preemption priority bits and the sub-priority `s` needs
+        // `log2((s + 1).next_power_of_two())` sub-priority bits, so this checks that there is a
+        // split that fits both.
+        let (check_priority, determine_prio_bits) = if let Some(prio_bits) = prio_bits {
+            (
+                quote! {
+                    const _: () = assert!(
+                        (__PRIORITY.get() as u32).next_power_of_two()
+                            * ((#sub_priority as u32) + 1).next_power_of_two()
+                            <= (1u32 << ((#prio_bits) as u32)),
+                        concat!(
+                            "Unsupported priority level ",
+                            stringify!(#priority),
+                            " was used for interrupt ",
+                            stringify!(#interrupt_path)
+                        )
+                    );
+                },
+                quote! {
+                    let prio_bits = ::cortex_m_interrupt::PrioBits::new((#prio_bits), ::cortex_m_interrupt::prigroup());
+                },
+            )
+        } else {
+            (
+                quote! {},
+                quote! {
+                    let mut nvic: ::cortex_m_interrupt::cortex_m::peripheral::NVIC = unsafe { core::mem::transmute(()) };
+                    let prio_bits = ::cortex_m_interrupt::determine_prio_bits(&mut nvic, #interrupt_path);
+                },
+            )
+        };
 
         let set_priority = if use_logical_priority {
             quote! {
-                 let prio_bits = ::cortex_m_interrupt::determine_prio_bits(&mut nvic, #interrupt_path);
-                 let priority = ::cortex_m_interrupt::logical2hw(self.priority, prio_bits);
+                 #determine_prio_bits
+                 let priority = ::cortex_m_interrupt::logical2hw_grouped(self.priority, self.sub_priority, prio_bits);
 
                  if let Some(priority) = priority {
-                    nvic.set_priority(#interrupt_path, priority);
+                    NvicBackend::set_priority(#interrupt_path, priority);
                  } else {
                     panic!(stringify!(Unsupported priority level #priority was used for interrupt #interrupt_path));
                  }
             }
         } else {
             quote! {
-                nvic.set_priority(#interrupt_path, self.priority);
+                NvicBackend::set_priority(#interrupt_path, self.priority.get());
+            }
+        };
+
+        let clear_pending = if clear_pending.as_ref().map(|v| v.value).unwrap_or(false) {
+            quote! {
+                NvicBackend::unpend(#interrupt_path);
             }
+        } else {
+            quote! {}
         };
 
-        let take_interrupt = crate::Take::new(interrupt_ident.ident.clone()).build();
+        if let Some(Target::NonSecure) = target {
+            // The Non-secure image occupies and enables the interrupt, on the core that it runs on.
+            let option = enable
+                .as_ref()
+                .map(LitBool::span)
+                .or_else(|| ram.as_ref().map(LitBool::span))
+                .or_else(|| core.as_ref().map(LitInt::span))
+                .or_else(|| cores.as_ref().map(LitInt::span));
+
+            if let Some(option) = option {
+                abort!(
+                    option,
+                    "This option can not be used with `target = non_secure`.";
+                    help = "Specify it where the Non-secure image takes this interrupt."
+                );
+            }
+
+            return Self::build_non_secure(
+                interrupt_path,
+                &interrupt_type,
+                &sub_priority,
+                define_priority,
+                check_priority,
+                set_priority,
+                clear_pending,
+            );
+        }
+
+        let set_target = if let Some(Target::Secure) = target {
+            quote! {
+                ::cortex_m_interrupt::set_target_state(#interrupt_path, ::cortex_m_interrupt::TargetState::Secure);
+            }
+        } else {
+            quote! {}
+        };
+
+        let unmask = if enable.as_ref().map(|v| v.value).unwrap_or(true) {
+            quote! {
+                NvicBackend::unmask(#interrupt_path);
+            }
+        } else {
+            quote! {}
+        };
+
+        let ram = ram.as_ref().map(|v| v.value).unwrap_or(false);
+        let cores = match (core, cores) {
+            (_, Some(cores)) => crate::Cores::Each(cores.clone()),
+            (Some(core), None) => crate::Cores::One(core.clone()),
+            (None, None) => crate::Cores::Any,
+        };
+        let take = crate::Take::new(
+            interrupt_ident.ident.clone(),
+            quote! { #interrupt_path },
+            quote! { #interrupt_type },
+            cores,
+            ram,
+        );
+        let assert_core = take.assert_core();
+        let generics = take.generics();
+        let generic_arguments = take.generic_arguments();
+        let core = take.core();
+        let take_interrupt = take.build();
+
+        // With `cores = K`, a registration is created for each core, each with its own `CORE`.
+        let registrations = if let Some(cores) = self.cores.as_ref() {
+            let cores = 0..cores.base10_parse::<u8>().unwrap_or_abort();
+            quote! {
+                (#(
+                    NvicInterruptRegistration::<#cores> {
+                        priority: __PRIORITY.logical(),
+                        sub_priority: #sub_priority,
+                    },
+                )*)
+            }
+        } else {
+            quote! {
+                NvicInterruptRegistration {
+                    priority: __PRIORITY.logical(),
+                    sub_priority: #sub_priority,
+                }
+            }
+        };
 
         quote! {{
-            struct NvicInterruptRegistration {
+            use ::cortex_m_interrupt::backend::{Active as NvicBackend, Backend as _};
+
+            #define_priority
+            #check_priority
+
+            #take_interrupt
+
+            struct NvicInterruptRegistration #generics {
                 priority: core::num::NonZeroU8,
+                sub_priority: u8,
             }
 
-            impl ::cortex_m_interrupt::InterruptRegistration for NvicInterruptRegistration {
+            impl #generics NvicInterruptRegistration #generic_arguments {
+                /// Configure the interrupt around `occupy`, which occupies `Handle`.
                 #[inline(always)]
-                fn occupy(self, f: fn()) {
-                    use ::cortex_m_interrupt::InterruptRegistration;
+                fn configure(self, occupy: impl FnOnce(Handle #generic_arguments)) {
+                    #assert_core
 
-                    ::cortex_m_interrupt::cortex_m::interrupt::free(|_| unsafe {
-                        let registration = #take_interrupt;
+                    ::cortex_m_interrupt::cs::with(|| unsafe {
+                        NvicBackend::mask(#interrupt_path);
 
-                        ::cortex_m_interrupt::cortex_m::peripheral::NVIC::mask(#interrupt_path);
+                        #set_target
 
-                        registration.occupy(f);
+                        occupy(Handle);
 
-                        let mut nvic: ::cortex_m_interrupt::cortex_m::peripheral::NVIC = unsafe { core::mem::transmute(()) };
                         #set_priority
-                        ::cortex_m_interrupt::cortex_m::peripheral::NVIC::unmask(#interrupt_path);
+                        #clear_pending
+                        #unmask
                     })
+                }
+            }
+
+            impl #generics ::cortex_m_interrupt::InterruptRegistration for NvicInterruptRegistration #generic_arguments {
+                #[inline(always)]
+                fn occupy(self, f: fn()) {
+                    self.configure(|handle| ::cortex_m_interrupt::InterruptRegistration::occupy(handle, f));
+                }
+            }
 
+            impl #generics ::cortex_m_interrupt::OccupyWith<#interrupt_type> for NvicInterruptRegistration #generic_arguments {
+                #[inline(always)]
+                fn occupy_with(self, f: fn(#interrupt_type)) {
+                    self.configure(|handle| ::cortex_m_interrupt::OccupyWith::occupy_with(handle, f));
                 }
             }
 
-            impl ::cortex_m_interrupt::NvicInterruptRegistration<#interrupt_type> for NvicInterruptRegistration {
+            impl #generics ::cortex_m_interrupt::NvicInterruptRegistration<#interrupt_type> for NvicInterruptRegistration #generic_arguments {
                 const INTERRUPT_NUMBER: #interrupt_type = #interrupt_path;
+                const CORE: Option<u8> = #core;
+
+                fn logical_priority(&self) -> core::num::NonZeroU8 {
+                    self.priority
+                }
+
+                fn logical_sub_priority(&self) -> u8 {
+                    self.sub_priority
+                }
+            }
+
+            #registrations
+        }}
+        .into()
+    }
+
+    /// Build a registration for an interrupt that is routed to the Non-secure state.
+    ///
+    /// No occupation is created, as the interrupt is serviced through the vector
+    /// table of the Non-secure image.
+    fn build_non_secure(
+        interrupt_path: &Path,
+        interrupt_type: &Type,
+        sub_priority: &proc_macro2::TokenStream,
+        define_priority: proc_macro2::TokenStream,
+        check_priority: proc_macro2::TokenStream,
+        set_priority: proc_macro2::TokenStream,
+        clear_pending: proc_macro2::TokenStream,
+    ) -> proc_macro::TokenStream {
+        quote! {{
+            use ::cortex_m_interrupt::backend::{Active as NvicBackend, Backend as _};
+
+            #define_priority
+            #check_priority
+
+            struct NonSecureRegistration {
+                priority: core::num::NonZeroU8,
+                sub_priority: u8,
+            }
+
+            impl ::cortex_m_interrupt::NonSecureRegistration<#interrupt_type> for NonSecureRegistration {
+                const INTERRUPT_NUMBER: #interrupt_type = #interrupt_path;
+
+                fn logical_priority(&self) -> core::num::NonZeroU8 {
+                    self.priority
+                }
+
+                fn logical_sub_priority(&self) -> u8 {
+                    self.sub_priority
+                }
+
+                #[inline(always)]
+                fn route(self) {
+                    static ROUTED: ::cortex_m_interrupt::atomic_polyfill::AtomicBool = ::cortex_m_interrupt::atomic_polyfill::AtomicBool::new(false);
+
+                    if ROUTED.swap(true, core::sync::atomic::Ordering::Acquire) {
+                        panic!(stringify!(Attempted to route already-routed interrupt #interrupt_path))
+                    }
+
+                    ::cortex_m_interrupt::cs::with(|| unsafe {
+                        NvicBackend::mask(#interrupt_path);
+
+                        ::cortex_m_interrupt::set_target_state(#interrupt_path, ::cortex_m_interrupt::TargetState::NonSecure);
+
+                        #set_priority
+                        #clear_pending
+                    })
+                }
             }
 
-            NvicInterruptRegistration {
-                // Note(unwrap): the macro verifies that `#priority` is not 0.
-                priority: core::num::NonZeroU8::new(#priority).unwrap(),
+            NonSecureRegistration {
+                priority: __PRIORITY.logical(),
+                sub_priority: #sub_priority,
             }
         }}
         .into()
@@ -0,0 +1,10 @@
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    // Put `memory.x` in the search path of the linker.
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out.join("memory.x"), include_bytes!("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+
+    println!("cargo:rerun-if-changed=memory.x");
+}
@@ -0,0 +1,83 @@
+//! Occupy NVIC interrupts with different priorities, pend them from software, and check that
+//! they run at the configured priorities and nest accordingly.
+#![no_std]
+#![no_main]
+
+use cortex_m_interrupt::{
+    occupy, take_nvic_interrupt, InterruptRegistration, NvicInterruptRegistration,
+};
+use cortex_m_interrupt_tests::{assert_priority, pass, pend, settle, Log};
+use lm3s6965::{Interrupt, NVIC_PRIO_BITS};
+
+static LOG: Log = Log::new();
+
+// `GPIOA` (1) is preempted by `GPIOC` (2), which is preempted by `GPIOB` (3). `UART0` (1) is
+// pended by `GPIOB`, but can not preempt `GPIOA`, so it only runs after `GPIOA` is done.
+fn gpioa() {
+    LOG.push(b'A');
+    assert_priority(1);
+
+    pend(Interrupt::GPIOC);
+
+    LOG.push(b'a');
+}
+
+fn gpiob() {
+    LOG.push(b'B');
+    assert_priority(3);
+
+    pend(Interrupt::UART0);
+
+    LOG.push(b'b');
+}
+
+fn gpioc() {
+    LOG.push(b'C');
+    assert_priority(2);
+
+    pend(Interrupt::GPIOB);
+
+    LOG.push(b'c');
+}
+
+#[occupy(Interrupt::UART0, 1, prio_bits = NVIC_PRIO_BITS, ram = true)]
+fn uart0() {
+    LOG.push(b'U');
+    assert_priority(1);
+    LOG.push(b'u');
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    take_nvic_interrupt!(Interrupt::GPIOA, 1).occupy(gpioa);
+    take_nvic_interrupt!(Interrupt::GPIOB, 3, prio_bits = NVIC_PRIO_BITS).occupy(gpiob);
+    take_nvic_interrupt!(Interrupt::GPIOC, priority = 2).occupy(gpioc);
+    let uart0 = uart0();
+
+    pend(Interrupt::GPIOA);
+    LOG.assert_eq("ACBbcaUu");
+
+    // A masked interrupt only runs once it is unmasked again.
+    uart0.masked(|| {
+        pend(Interrupt::UART0);
+        LOG.assert_eq("ACBbcaUu");
+    });
+    settle();
+    LOG.assert_eq("ACBbcaUuUu");
+
+    // An interrupt that is occupied with `enable = false` only runs once it is unmasked.
+    let occupation = take_nvic_interrupt!(Interrupt::SSI0, 1, enable = false).occupy_nvic(|| {
+        LOG.push(b'S');
+        LOG.push(b's');
+    });
+
+    pend(Interrupt::SSI0);
+    LOG.assert_eq("ACBbcaUuUu");
+
+    // NOTE(unsafe): the interrupt is not used in a mask-based critical section.
+    unsafe { occupation.unmask() };
+    settle();
+    LOG.assert_eq("ACBbcaUuUuSs");
+
+    pass("nvic");
+}
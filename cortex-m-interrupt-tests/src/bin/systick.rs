@@ -0,0 +1,47 @@
+//! Occupy SysTick, and check that the occupation runs both when the SysTick timer wraps, and
+//! when SysTick is pended from software.
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::peripheral::{syst::SystClkSource, SCB};
+use cortex_m_interrupt::{take_exception, InterruptRegistration};
+use cortex_m_interrupt_tests::{assert_priority, pass, settle};
+use lm3s6965::NVIC_PRIO_BITS;
+
+static TICKS: AtomicU32 = AtomicU32::new(0);
+
+fn systick() {
+    // SysTick has the highest priority after reset.
+    assert_priority(1 << NVIC_PRIO_BITS);
+
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let mut cp = cortex_m::Peripherals::take().unwrap();
+
+    take_exception!(SysTick).occupy(systick);
+
+    cp.SYST.set_clock_source(SystClkSource::Core);
+    cp.SYST.set_reload(100_000);
+    cp.SYST.clear_current();
+    cp.SYST.enable_interrupt();
+    cp.SYST.enable_counter();
+
+    while TICKS.load(Ordering::Relaxed) < 3 {}
+
+    cp.SYST.disable_interrupt();
+    cp.SYST.disable_counter();
+
+    let ticks = TICKS.load(Ordering::Relaxed);
+
+    SCB::set_pendst();
+    settle();
+
+    assert_eq!(TICKS.load(Ordering::Relaxed), ticks + 1);
+
+    pass("systick");
+}
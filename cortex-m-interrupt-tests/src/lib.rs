@@ -0,0 +1,90 @@
+//! Helpers shared by the QEMU integration tests.
+#![no_std]
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use cortex_m::peripheral::NVIC;
+use cortex_m_interrupt::{current_logical_priority, Priority};
+use cortex_m_semihosting::{debug, hprintln};
+use lm3s6965::{Interrupt, NVIC_PRIO_BITS};
+use panic_semihosting as _;
+
+/// The amount of entries that a [`Log`] can hold.
+const ENTRIES: usize = 32;
+
+/// A log of the order in which occupations ran.
+///
+/// Occupations push an uppercase tag when they are entered, and the lowercase tag when they
+/// are left, so that nesting shows up as e.g. `ABba`.
+pub struct Log {
+    entries: [AtomicU8; ENTRIES],
+    len: AtomicUsize,
+}
+
+impl Log {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicU8 = AtomicU8::new(0);
+
+        Self {
+            entries: [EMPTY; ENTRIES],
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Push `tag` onto the log.
+    pub fn push(&self, tag: u8) {
+        let index = self.len.fetch_add(1, Ordering::Relaxed);
+        self.entries[index].store(tag, Ordering::Relaxed);
+    }
+
+    /// Assert that exactly `expected` was pushed onto the log.
+    pub fn assert_eq(&self, expected: &str) {
+        let len = self.len.load(Ordering::Relaxed);
+        let mut log = [0u8; ENTRIES];
+        for (tag, entry) in log.iter_mut().zip(&self.entries[..len]) {
+            *tag = entry.load(Ordering::Relaxed);
+        }
+
+        let log = core::str::from_utf8(&log[..len]).unwrap();
+        assert_eq!(log, expected, "occupations ran in an unexpected order");
+    }
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Assert that the code that is currently executing runs at logical priority `logical`.
+pub fn assert_priority(logical: u8) {
+    assert_eq!(
+        current_logical_priority(NVIC_PRIO_BITS),
+        Some(Priority::new(logical)),
+        "occupation runs at an unexpected priority"
+    );
+}
+
+/// Pend `interrupt`, and wait until the pend has taken effect, so that the interrupt has run
+/// before this function returns if it can preempt the code that is currently executing.
+pub fn pend(interrupt: Interrupt) {
+    NVIC::pend(interrupt);
+    settle();
+}
+
+/// Wait until changes to the NVIC (such as unmasking a pending interrupt) have taken effect.
+pub fn settle() {
+    cortex_m::asm::dsb();
+    cortex_m::asm::isb();
+}
+
+/// Report that the test passed, and exit QEMU.
+pub fn pass(test: &str) -> ! {
+    hprintln!("{}: ok", test);
+    debug::exit(debug::EXIT_SUCCESS);
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
@@ -0,0 +1,19 @@
+use std::env;
+
+fn main() {
+    let target = env::var("TARGET").unwrap();
+
+    println!("cargo:rustc-check-cfg=cfg(has_basepri)");
+    println!("cargo:rustc-check-cfg=cfg(has_prigroup)");
+    println!("cargo:rustc-check-cfg=cfg(loom)");
+
+    // ARMv6-M and ARMv8-M Baseline do not implement the BASEPRI register, nor priority grouping
+    // (`AIRCR.PRIGROUP` is reserved there).
+    if target.starts_with("thumbv7m-")
+        || target.starts_with("thumbv7em-")
+        || target.starts_with("thumbv8m.main")
+    {
+        println!("cargo:rustc-cfg=has_basepri");
+        println!("cargo:rustc-cfg=has_prigroup");
+    }
+}
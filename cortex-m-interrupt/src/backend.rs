@@ -0,0 +1,604 @@
+//! Access to the NVIC and SCB registers used by this crate and the code generated by its macros.
+//!
+//! All register accesses go through the [`Backend`] that is [`Active`]. On ARM targets, this is
+//! [`CortexM`], which accesses the registers of the core. On all other targets, this is
+//! [`Simulated`], which simulates the NVIC and SCB in memory, so that registrations and their
+//! occupations can be tested on the host.
+
+use atomic_polyfill::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use cortex_m::{interrupt::InterruptNumber, peripheral::scb::VectActive};
+
+use crate::TargetState;
+
+/// The NVIC and SCB register accesses used by this crate.
+pub trait Backend {
+    /// Mask `interrupt`.
+    fn mask<I: InterruptNumber>(interrupt: I);
+
+    /// Unmask `interrupt`.
+    ///
+    /// # Safety
+    /// Unmasking an interrupt can break mask-based critical sections.
+    unsafe fn unmask<I: InterruptNumber>(interrupt: I);
+
+    /// Whether `interrupt` is unmasked.
+    fn is_enabled<I: InterruptNumber>(interrupt: I) -> bool;
+
+    /// Set the pending state of `interrupt`.
+    fn pend<I: InterruptNumber>(interrupt: I);
+
+    /// Clear the pending state of `interrupt`.
+    fn unpend<I: InterruptNumber>(interrupt: I);
+
+    /// Whether `interrupt` is pending.
+    fn is_pending<I: InterruptNumber>(interrupt: I) -> bool;
+
+    /// Set the hardware priority of `interrupt` to `hw`.
+    ///
+    /// # Safety
+    /// Changing the priority of an interrupt can break priority-based critical sections.
+    unsafe fn set_priority<I: InterruptNumber>(interrupt: I, hw: u8);
+
+    /// The hardware priority of `interrupt`.
+    fn priority<I: InterruptNumber>(interrupt: I) -> u8;
+
+    /// The hardware priority of the system exception with exception number `number`,
+    /// which is in the range `4..=15`.
+    fn system_handler_priority(number: u8) -> u8;
+
+    /// Set the security state that `interrupt` targets (its bit in `NVIC_ITNS`).
+    ///
+    /// # Safety
+    /// See [`set_target_state`](crate::set_target_state).
+    unsafe fn set_target_state<I: InterruptNumber>(interrupt: I, state: TargetState);
+
+    /// The security state that `interrupt` targets (its bit in `NVIC_ITNS`).
+    ///
+    /// # Safety
+    /// See [`target_state`](crate::target_state).
+    unsafe fn target_state<I: InterruptNumber>(interrupt: I) -> TargetState;
+
+    /// The priority grouping (`AIRCR.PRIGROUP`). Cores that do not implement priority grouping
+    /// (ARMv6-M and ARMv8-M Baseline) read it as `0`.
+    fn prigroup() -> u8;
+
+    /// Set the priority grouping (`AIRCR.PRIGROUP`). This is ignored on cores that do not
+    /// implement priority grouping.
+    ///
+    /// # Safety
+    /// See [`set_prigroup`](crate::set_prigroup).
+    unsafe fn set_prigroup(prigroup: u8);
+
+    /// The priority mask (`BASEPRI`).
+    ///
+    /// Cores that do not implement `BASEPRI` (ARMv6-M and ARMv8-M Baseline) read it as `0`, which
+    /// does not mask any interrupts.
+    fn basepri() -> u8;
+
+    /// Set the priority mask (`BASEPRI`) to `basepri`. This is ignored on cores that do not
+    /// implement `BASEPRI`.
+    ///
+    /// # Safety
+    /// Lowering the priority mask can break priority-based critical sections.
+    unsafe fn set_basepri(basepri: u8);
+
+    /// Raise the priority mask to `basepri` (`BASEPRI_MAX`), unless `basepri` is `0` or the
+    /// current priority mask already masks all interrupts that `basepri` masks. This is ignored
+    /// on cores that do not implement `BASEPRI`.
+    fn set_basepri_max(basepri: u8);
+
+    /// The exception number of the vector that is currently executing (`ICSR.VECTACTIVE`).
+    ///
+    /// This is `0` in thread mode, and `16 + n` for interrupt `n`. As ARMv8-M supports up to 480
+    /// interrupts, it does not always fit in a [`VectActive`].
+    fn vect_active() -> u16;
+
+    /// Ensure that all preceding register writes have taken effect.
+    fn barrier();
+
+    /// Execute `f` with all interrupts disabled.
+    fn free<R>(f: impl FnOnce() -> R) -> R;
+}
+
+/// The [`Backend`] that is used by this crate.
+#[cfg(target_arch = "arm")]
+pub type Active = CortexM;
+
+/// The [`Backend`] that is used by this crate.
+#[cfg(not(target_arch = "arm"))]
+pub type Active = Simulated;
+
+/// The [`Backend`] that accesses the registers of a Cortex-M core.
+pub struct CortexM;
+
+/// The address of the first Interrupt Target Non-secure Register (`NVIC_ITNS0`).
+const NVIC_ITNS: *mut u32 = 0xE000_E380 as *mut u32;
+
+impl Backend for CortexM {
+    fn mask<I: InterruptNumber>(interrupt: I) {
+        cortex_m::peripheral::NVIC::mask(interrupt)
+    }
+
+    unsafe fn unmask<I: InterruptNumber>(interrupt: I) {
+        cortex_m::peripheral::NVIC::unmask(interrupt)
+    }
+
+    fn is_enabled<I: InterruptNumber>(interrupt: I) -> bool {
+        cortex_m::peripheral::NVIC::is_enabled(interrupt)
+    }
+
+    fn pend<I: InterruptNumber>(interrupt: I) {
+        cortex_m::peripheral::NVIC::pend(interrupt)
+    }
+
+    fn unpend<I: InterruptNumber>(interrupt: I) {
+        cortex_m::peripheral::NVIC::unpend(interrupt)
+    }
+
+    fn is_pending<I: InterruptNumber>(interrupt: I) -> bool {
+        cortex_m::peripheral::NVIC::is_pending(interrupt)
+    }
+
+    unsafe fn set_priority<I: InterruptNumber>(interrupt: I, hw: u8) {
+        // NOTE(unsafe): `NVIC` is a zero-sized token, and setting the priority
+        // of an interrupt is a single register write.
+        let mut nvic: cortex_m::peripheral::NVIC = core::mem::transmute(());
+        nvic.set_priority(interrupt, hw)
+    }
+
+    fn priority<I: InterruptNumber>(interrupt: I) -> u8 {
+        cortex_m::peripheral::NVIC::get_priority(interrupt)
+    }
+
+    fn system_handler_priority(number: u8) -> u8 {
+        /// The address of the first System Handler Priority Register (`SHPR1`).
+        const SHPR: *const u32 = 0xE000_ED18 as *const u32;
+
+        let number = number as usize;
+
+        // NOTE(unsafe): atomic read without side effects. The registers are read as words,
+        // as byte accesses are not supported on ARMv6-M.
+        let shpr = unsafe { core::ptr::read_volatile(SHPR.add((number - 4) / 4)) };
+        (shpr >> (8 * (number % 4))) as u8
+    }
+
+    unsafe fn set_target_state<I: InterruptNumber>(interrupt: I, state: TargetState) {
+        let number = interrupt.number() as usize;
+        let register = NVIC_ITNS.add(number / 32);
+        let bit = 1 << (number % 32);
+
+        let value = core::ptr::read_volatile(register);
+        let value = match state {
+            TargetState::Secure => value & !bit,
+            TargetState::NonSecure => value | bit,
+        };
+        core::ptr::write_volatile(register, value);
+    }
+
+    unsafe fn target_state<I: InterruptNumber>(interrupt: I) -> TargetState {
+        let number = interrupt.number() as usize;
+        let value = core::ptr::read_volatile(NVIC_ITNS.add(number / 32));
+
+        if value & (1 << (number % 32)) != 0 {
+            TargetState::NonSecure
+        } else {
+            TargetState::Secure
+        }
+    }
+
+    #[cfg(has_prigroup)]
+    fn prigroup() -> u8 {
+        // NOTE(unsafe): atomic read without side effects.
+        let aircr = unsafe { (*cortex_m::peripheral::SCB::PTR).aircr.read() };
+        ((aircr >> 8) & 0b111) as u8
+    }
+
+    #[cfg(not(has_prigroup))]
+    fn prigroup() -> u8 {
+        0
+    }
+
+    #[cfg(has_prigroup)]
+    unsafe fn set_prigroup(prigroup: u8) {
+        const VECTKEY: u32 = 0x05FA << 16;
+        const PRIGROUP: u32 = 0b111 << 8;
+
+        // Writes without `VECTKEY` are ignored, and the upper half reads back as `VECTKEYSTAT`
+        // instead. The other fields (such as `PRIS`, `BFHFNMINS` and `SYSRESETREQS` on ARMv8-M)
+        // are written back as they are.
+        let aircr = &(*cortex_m::peripheral::SCB::PTR).aircr;
+        let other = aircr.read() & !(0xFFFF << 16) & !PRIGROUP;
+        aircr.write(VECTKEY | other | (((prigroup as u32) << 8) & PRIGROUP));
+    }
+
+    #[cfg(not(has_prigroup))]
+    unsafe fn set_prigroup(_prigroup: u8) {}
+
+    #[cfg(has_basepri)]
+    fn basepri() -> u8 {
+        cortex_m::register::basepri::read()
+    }
+
+    #[cfg(not(has_basepri))]
+    fn basepri() -> u8 {
+        0
+    }
+
+    #[cfg(has_basepri)]
+    unsafe fn set_basepri(basepri: u8) {
+        cortex_m::register::basepri::write(basepri)
+    }
+
+    #[cfg(not(has_basepri))]
+    unsafe fn set_basepri(_basepri: u8) {}
+
+    #[cfg(has_basepri)]
+    fn set_basepri_max(basepri: u8) {
+        cortex_m::register::basepri_max::write(basepri)
+    }
+
+    #[cfg(not(has_basepri))]
+    fn set_basepri_max(_basepri: u8) {}
+
+    fn vect_active() -> u16 {
+        // NOTE(unsafe): atomic read without side effects.
+        let icsr = unsafe { (*cortex_m::peripheral::SCB::PTR).icsr.read() };
+        (icsr & 0x1FF) as u16
+    }
+
+    fn barrier() {
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+    }
+
+    fn free<R>(f: impl FnOnce() -> R) -> R {
+        cortex_m::interrupt::free(|_| f())
+    }
+}
+
+/// A register access performed on the [`Simulated`] backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// An interrupt was masked.
+    Mask(u16),
+    /// An interrupt was unmasked.
+    Unmask(u16),
+    /// An interrupt was pended.
+    Pend(u16),
+    /// An interrupt was unpended.
+    Unpend(u16),
+    /// The hardware priority of an interrupt was set.
+    SetPriority(u16, u8),
+    /// The priority grouping was set.
+    SetPrigroup(u8),
+    /// The security state that an interrupt targets was set.
+    SetTargetState(u16, TargetState),
+    /// The priority mask (`BASEPRI`) was set to the given value.
+    SetBasepri(u8),
+    /// A critical section was entered.
+    Acquire,
+    /// A critical section was exited.
+    Release,
+}
+
+impl Event {
+    fn encode(self) -> u32 {
+        let (kind, number, value) = match self {
+            Event::Mask(number) => (0, number, 0),
+            Event::Unmask(number) => (1, number, 0),
+            Event::Pend(number) => (2, number, 0),
+            Event::Unpend(number) => (3, number, 0),
+            Event::SetPriority(number, hw) => (4, number, hw),
+            Event::SetPrigroup(prigroup) => (5, 0, prigroup),
+            Event::Acquire => (6, 0, 0),
+            Event::Release => (7, 0, 0),
+            Event::SetTargetState(number, TargetState::Secure) => (8, number, 0),
+            Event::SetTargetState(number, TargetState::NonSecure) => (8, number, 1),
+            Event::SetBasepri(basepri) => (9, 0, basepri),
+        };
+
+        (kind << 24) | ((number as u32) << 8) | value as u32
+    }
+
+    fn decode(event: u32) -> Self {
+        let number = (event >> 8) as u16;
+        let value = event as u8;
+
+        match event >> 24 {
+            0 => Event::Mask(number),
+            1 => Event::Unmask(number),
+            2 => Event::Pend(number),
+            3 => Event::Unpend(number),
+            4 => Event::SetPriority(number, value),
+            5 => Event::SetPrigroup(value),
+            6 => Event::Acquire,
+            7 => Event::Release,
+            9 => Event::SetBasepri(value),
+            _ if value == 0 => Event::SetTargetState(number, TargetState::Secure),
+            _ => Event::SetTargetState(number, TargetState::NonSecure),
+        }
+    }
+}
+
+/// The amount of interrupts that the [`Simulated`] NVIC implements.
+const INTERRUPTS: usize = 480;
+
+/// The amount of [`Event`]s that the [`Simulated`] backend records.
+const EVENTS: usize = 64;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_U8: AtomicU8 = AtomicU8::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_U32: AtomicU32 = AtomicU32::new(0);
+
+/// The state of the [`Simulated`] NVIC and SCB.
+struct State {
+    implemented_bits: AtomicU8,
+    enabled: [AtomicU32; INTERRUPTS / 32],
+    pending: [AtomicU32; INTERRUPTS / 32],
+    non_secure: [AtomicU32; INTERRUPTS / 32],
+    priorities: [AtomicU8; INTERRUPTS],
+    system_handler_priorities: [AtomicU8; 12],
+    prigroup: AtomicU8,
+    basepri: AtomicU8,
+    vect_active: AtomicU16,
+    critical_section: AtomicBool,
+    events: [AtomicU32; EVENTS],
+    event_count: AtomicUsize,
+}
+
+static STATE: State = State {
+    implemented_bits: AtomicU8::new(4),
+    enabled: [ZERO_U32; INTERRUPTS / 32],
+    pending: [ZERO_U32; INTERRUPTS / 32],
+    non_secure: [ZERO_U32; INTERRUPTS / 32],
+    priorities: [ZERO_U8; INTERRUPTS],
+    system_handler_priorities: [ZERO_U8; 12],
+    prigroup: AtomicU8::new(0),
+    basepri: AtomicU8::new(0),
+    vect_active: AtomicU16::new(0),
+    critical_section: AtomicBool::new(false),
+    events: [ZERO_U32; EVENTS],
+    event_count: AtomicUsize::new(0),
+};
+
+/// A [`Backend`] that simulates an NVIC and SCB in memory.
+///
+/// All register accesses are recorded as [`Event`]s (up to a maximum of 64), which can be
+/// inspected using [`Simulated::events`]. Only a single simulated NVIC exists, so tests that
+/// use it should not run concurrently.
+///
+/// The simulated NVIC implements 480 interrupts (the maximum of ARMv8-M), and 4 priority bits
+/// unless configured otherwise using [`Simulated::set_implemented_bits`].
+pub struct Simulated;
+
+impl Simulated {
+    /// Reset the simulated NVIC and SCB, and clear all recorded events.
+    ///
+    /// The amount of implemented priority bits is reset to 4.
+    pub fn reset() {
+        let state = &STATE;
+
+        state.implemented_bits.store(4, Ordering::Relaxed);
+        for word in state
+            .enabled
+            .iter()
+            .chain(&state.pending)
+            .chain(&state.non_secure)
+        {
+            word.store(0, Ordering::Relaxed);
+        }
+        for priority in state
+            .priorities
+            .iter()
+            .chain(&state.system_handler_priorities)
+        {
+            priority.store(0, Ordering::Relaxed);
+        }
+        state.prigroup.store(0, Ordering::Relaxed);
+        state.basepri.store(0, Ordering::Relaxed);
+        state.vect_active.store(0, Ordering::Relaxed);
+        state.critical_section.store(false, Ordering::Relaxed);
+        state.event_count.store(0, Ordering::Release);
+    }
+
+    /// Set the amount of priority bits that the simulated NVIC implements.
+    ///
+    /// Priority bits that are not implemented read back as zero.
+    pub fn set_implemented_bits(bits: u8) {
+        STATE.implemented_bits.store(bits.min(8), Ordering::Relaxed);
+    }
+
+    /// The amount of priority bits that the simulated NVIC implements.
+    pub fn implemented_bits() -> u8 {
+        STATE.implemented_bits.load(Ordering::Relaxed)
+    }
+
+    /// Set the hardware priority of the system exception with exception number `number`.
+    pub fn set_system_handler_priority(number: u8, hw: u8) {
+        STATE.system_handler_priorities[number as usize - 4]
+            .store(hw & Self::implemented_mask(), Ordering::Relaxed);
+    }
+
+    /// Set the vector that is currently executing, as returned by [`Backend::vect_active`].
+    pub fn set_vect_active(vector: VectActive) {
+        let number = match vector {
+            VectActive::ThreadMode => 0,
+            VectActive::Exception(exception) => (16 + exception.irqn() as i16) as u16,
+            VectActive::Interrupt { irqn } => irqn as u16 + 16,
+        };
+
+        STATE.vect_active.store(number, Ordering::Relaxed);
+    }
+
+    /// Whether a critical section is currently active.
+    pub fn in_critical_section() -> bool {
+        STATE.critical_section.load(Ordering::Relaxed)
+    }
+
+    /// The events that have been recorded since the last [`Simulated::reset`].
+    pub fn events() -> impl Iterator<Item = Event> {
+        let count = STATE.event_count.load(Ordering::Acquire).min(EVENTS);
+
+        STATE.events[..count]
+            .iter()
+            .map(|event| Event::decode(event.load(Ordering::Relaxed)))
+    }
+
+    fn record(event: Event) {
+        let index = STATE.event_count.fetch_add(1, Ordering::AcqRel);
+        if let Some(slot) = STATE.events.get(index) {
+            slot.store(event.encode(), Ordering::Relaxed);
+        }
+    }
+
+    fn implemented_mask() -> u8 {
+        let bits = STATE.implemented_bits.load(Ordering::Relaxed);
+        (0xFF00u16 >> bits) as u8
+    }
+
+    fn bit<I: InterruptNumber>(interrupt: I) -> (usize, u32) {
+        let number = interrupt.number() as usize;
+        (number / 32, 1 << (number % 32))
+    }
+}
+
+impl Backend for Simulated {
+    fn mask<I: InterruptNumber>(interrupt: I) {
+        let (word, bit) = Self::bit(interrupt);
+        STATE.enabled[word].fetch_and(!bit, Ordering::AcqRel);
+        Self::record(Event::Mask(interrupt.number()));
+    }
+
+    unsafe fn unmask<I: InterruptNumber>(interrupt: I) {
+        let (word, bit) = Self::bit(interrupt);
+        STATE.enabled[word].fetch_or(bit, Ordering::AcqRel);
+        Self::record(Event::Unmask(interrupt.number()));
+    }
+
+    fn is_enabled<I: InterruptNumber>(interrupt: I) -> bool {
+        let (word, bit) = Self::bit(interrupt);
+        STATE.enabled[word].load(Ordering::Acquire) & bit != 0
+    }
+
+    fn pend<I: InterruptNumber>(interrupt: I) {
+        let (word, bit) = Self::bit(interrupt);
+        STATE.pending[word].fetch_or(bit, Ordering::AcqRel);
+        Self::record(Event::Pend(interrupt.number()));
+    }
+
+    fn unpend<I: InterruptNumber>(interrupt: I) {
+        let (word, bit) = Self::bit(interrupt);
+        STATE.pending[word].fetch_and(!bit, Ordering::AcqRel);
+        Self::record(Event::Unpend(interrupt.number()));
+    }
+
+    fn is_pending<I: InterruptNumber>(interrupt: I) -> bool {
+        let (word, bit) = Self::bit(interrupt);
+        STATE.pending[word].load(Ordering::Acquire) & bit != 0
+    }
+
+    unsafe fn set_priority<I: InterruptNumber>(interrupt: I, hw: u8) {
+        STATE.priorities[interrupt.number() as usize]
+            .store(hw & Self::implemented_mask(), Ordering::Relaxed);
+        Self::record(Event::SetPriority(interrupt.number(), hw));
+    }
+
+    fn priority<I: InterruptNumber>(interrupt: I) -> u8 {
+        STATE.priorities[interrupt.number() as usize].load(Ordering::Relaxed)
+    }
+
+    fn system_handler_priority(number: u8) -> u8 {
+        STATE.system_handler_priorities[number as usize - 4].load(Ordering::Relaxed)
+    }
+
+    unsafe fn set_target_state<I: InterruptNumber>(interrupt: I, state: TargetState) {
+        let (word, bit) = Self::bit(interrupt);
+        match state {
+            TargetState::Secure => STATE.non_secure[word].fetch_and(!bit, Ordering::AcqRel),
+            TargetState::NonSecure => STATE.non_secure[word].fetch_or(bit, Ordering::AcqRel),
+        };
+        Self::record(Event::SetTargetState(interrupt.number(), state));
+    }
+
+    unsafe fn target_state<I: InterruptNumber>(interrupt: I) -> TargetState {
+        let (word, bit) = Self::bit(interrupt);
+        if STATE.non_secure[word].load(Ordering::Acquire) & bit != 0 {
+            TargetState::NonSecure
+        } else {
+            TargetState::Secure
+        }
+    }
+
+    fn prigroup() -> u8 {
+        STATE.prigroup.load(Ordering::Relaxed)
+    }
+
+    unsafe fn set_prigroup(prigroup: u8) {
+        STATE.prigroup.store(prigroup & 0b111, Ordering::Relaxed);
+        Self::record(Event::SetPrigroup(prigroup & 0b111));
+    }
+
+    fn basepri() -> u8 {
+        STATE.basepri.load(Ordering::Relaxed)
+    }
+
+    unsafe fn set_basepri(basepri: u8) {
+        let basepri = basepri & Self::implemented_mask();
+        STATE.basepri.store(basepri, Ordering::Relaxed);
+        Self::record(Event::SetBasepri(basepri));
+    }
+
+    fn set_basepri_max(basepri: u8) {
+        let basepri = basepri & Self::implemented_mask();
+        let raised = STATE
+            .basepri
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                // A lower non-zero value masks more interrupts.
+                (basepri != 0 && (current == 0 || basepri < current)).then_some(basepri)
+            });
+
+        if raised.is_ok() {
+            Self::record(Event::SetBasepri(basepri));
+        }
+    }
+
+    fn vect_active() -> u16 {
+        STATE.vect_active.load(Ordering::Relaxed)
+    }
+
+    fn barrier() {}
+
+    fn free<R>(f: impl FnOnce() -> R) -> R {
+        let nested = STATE.critical_section.swap(true, Ordering::AcqRel);
+        Self::record(Event::Acquire);
+
+        let r = f();
+
+        Self::record(Event::Release);
+        STATE.critical_section.store(nested, Ordering::Release);
+
+        r
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn events() {
+    let events = [
+        Event::Mask(239),
+        Event::Unmask(0),
+        Event::Pend(16),
+        Event::Unpend(17),
+        Event::SetPriority(42, 0xE0),
+        Event::SetPrigroup(5),
+        Event::Acquire,
+        Event::Release,
+        Event::SetTargetState(479, TargetState::NonSecure),
+        Event::SetBasepri(0x40),
+    ];
+
+    for event in events {
+        assert_eq!(Event::decode(event.encode()), event);
+    }
+}
@@ -0,0 +1,358 @@
+use core::{cell::UnsafeCell, marker::PhantomData, num::NonZeroU8};
+
+use cortex_m::interrupt::InterruptNumber;
+
+use crate::{
+    sync::{AtomicBool, Ordering},
+    Handler, InterruptRegistration, NvicInterruptRegistration, OccupyWith,
+};
+
+/// The storage of an interrupt whose occupation is a list of up to `N` occupations, which are
+/// all run every time the interrupt fires.
+///
+/// A handler list is created using [`chain_interrupt`], which returns one [`ChainedRegistration`]
+/// for each position in the list. The occupations are run in the order of their positions,
+/// starting at position `0`. Positions that are not occupied are skipped.
+///
+/// [`chain_interrupt`]: crate::chain_interrupt
+pub struct HandlerList<const N: usize> {
+    occupations: [Handler; N],
+    run: UnsafeCell<unsafe fn(&Handler) -> bool>,
+    chained: AtomicBool,
+}
+
+impl<const N: usize> HandlerList<N> {
+    /// Create an empty handler list.
+    #[cfg(not(loom))]
+    pub const fn new() -> Self {
+        Self {
+            occupations: [const { Handler::new() }; N],
+            run: UnsafeCell::new(Handler::run_nothing),
+            chained: AtomicBool::new(false),
+        }
+    }
+
+    /// Create an empty handler list.
+    #[cfg(loom)]
+    pub fn new() -> Self {
+        Self {
+            occupations: core::array::from_fn(|_| Handler::new()),
+            run: UnsafeCell::new(Handler::run_nothing),
+            chained: AtomicBool::new(false),
+        }
+    }
+
+    /// Occupy `registration` with `dispatch`, and return a [`ChainedRegistration`] for each
+    /// position in this handler list.
+    ///
+    /// `dispatch` must call [`HandlerList::dispatch`] on `self`. As an occupation can not
+    /// capture any state, [`chain_interrupt`] should be used instead, which creates the handler
+    /// list together with its `dispatch` function.
+    ///
+    /// # Panics
+    /// If this handler list has been chained before, or if `registration` panics when it
+    /// is occupied.
+    ///
+    /// [`chain_interrupt`]: crate::chain_interrupt
+    pub fn chain<T, R>(
+        &'static self,
+        registration: R,
+        dispatch: fn(),
+    ) -> [ChainedRegistration<T, R, N>; N]
+    where
+        T: InterruptNumber,
+        R: NvicInterruptRegistration<T>,
+    {
+        if !self.claim() {
+            panic!("Attempted to chain a handler list more than once.");
+        }
+
+        self.chain_claimed(registration, dispatch)
+    }
+
+    /// Claim this handler list for [`HandlerList::chain_claimed`], if it has not been claimed
+    /// before.
+    pub(crate) fn claim(&self) -> bool {
+        !self.chained.swap(true, Ordering::AcqRel)
+    }
+
+    /// Occupy `registration` with `dispatch`, after this handler list was claimed using
+    /// [`HandlerList::claim`].
+    pub(crate) fn chain_claimed<T, R>(
+        &'static self,
+        registration: R,
+        dispatch: fn(),
+    ) -> [ChainedRegistration<T, R, N>; N]
+    where
+        T: InterruptNumber,
+        R: NvicInterruptRegistration<T>,
+    {
+        // NOTE(unsafe): `dispatch` only reads `run` after the interrupt has been occupied, which
+        // publishes this write, and we are the only ones writing it. The occupations in the list
+        // are passed the interrupt number of `registration`.
+        unsafe { *self.run.get() = Handler::run_nvic::<T, R> };
+
+        let priority = registration.logical_priority();
+        let sub_priority = registration.logical_sub_priority();
+
+        registration.occupy(dispatch);
+
+        core::array::from_fn(|position| ChainedRegistration {
+            list: self,
+            position,
+            priority,
+            sub_priority,
+            _registration: PhantomData,
+        })
+    }
+
+    /// Run all occupations in this handler list, in order.
+    ///
+    /// This is the occupation of the interrupt of the handler list.
+    pub fn dispatch(&self) {
+        self.dispatch_if(|_| true);
+    }
+
+    /// Run the occupations of all occupied positions for which `filter` returns `true`, in order.
+    pub(crate) fn dispatch_if(&self, filter: impl Fn(usize) -> bool) {
+        // NOTE(unsafe): `run` is only written before the interrupt is occupied.
+        let run = unsafe { *self.run.get() };
+
+        for (position, occupation) in self.occupations.iter().enumerate() {
+            if occupation.is_occupied() && filter(position) {
+                // NOTE(unsafe): `run` passes the argument that the occupations take.
+                unsafe { run(occupation) };
+            }
+        }
+    }
+}
+
+// NOTE(unsafe): `run` is only written once, before it can be read.
+unsafe impl<const N: usize> Sync for HandlerList<N> {}
+
+impl<const N: usize> Default for HandlerList<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The registration of a single position in a [`HandlerList`].
+///
+/// A [`ChainedRegistration`] has the interrupt number, priority and core of the registration that
+/// the handler list was chained to, so it can be passed to drivers that take an
+/// [`NvicInterruptRegistration`]. Note that an [`NvicOccupation`] of a [`ChainedRegistration`]
+/// masks the whole interrupt, and thereby all occupations in the handler list.
+///
+/// Unlike other registrations, a [`ChainedRegistration`] can be occupied again after its
+/// occupation is removed using [`ChainedOccupation::detach`].
+///
+/// The registrations of the sources of a [`SharedInterrupt`] are [`ChainedRegistration`]s as
+/// well (see [`SharedRegistration`]).
+///
+/// [`NvicOccupation`]: crate::NvicOccupation
+/// [`SharedInterrupt`]: crate::SharedInterrupt
+/// [`SharedRegistration`]: crate::SharedRegistration
+pub struct ChainedRegistration<T, R, const N: usize> {
+    list: &'static HandlerList<N>,
+    position: usize,
+    priority: NonZeroU8,
+    sub_priority: u8,
+    _registration: PhantomData<(T, R)>,
+}
+
+impl<T, R, const N: usize> ChainedRegistration<T, R, N> {
+    /// The position of this registration in its [`HandlerList`].
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Occupy this registration with `f`, and return a [`ChainedOccupation`] that can be used
+    /// to remove `f` from the handler list again.
+    pub fn attach(self, f: fn()) -> ChainedOccupation<T, R, N> {
+        occupied(self.list.occupations[self.position].occupy(f));
+        ChainedOccupation { registration: self }
+    }
+}
+
+impl<T, R, const N: usize> ChainedRegistration<T, R, N>
+where
+    T: InterruptNumber,
+    R: NvicInterruptRegistration<T>,
+{
+    /// Occupy this registration with `f`, which is passed the interrupt number every time it
+    /// runs, and return a [`ChainedOccupation`] that can be used to remove `f` from the handler
+    /// list again.
+    pub fn attach_with_number(self, f: fn(T)) -> ChainedOccupation<T, R, N> {
+        // NOTE(unsafe): the occupations in the list are passed the interrupt number of `R`.
+        occupied(unsafe { self.list.occupations[self.position].occupy_with(f) });
+        ChainedOccupation { registration: self }
+    }
+}
+
+impl<T, R, const N: usize> InterruptRegistration for ChainedRegistration<T, R, N> {
+    fn occupy(self, f: fn()) {
+        self.attach(f);
+    }
+}
+
+impl<T, R, const N: usize> OccupyWith<T> for ChainedRegistration<T, R, N>
+where
+    T: InterruptNumber,
+    R: NvicInterruptRegistration<T>,
+{
+    fn occupy_with(self, f: fn(T)) {
+        self.attach_with_number(f);
+    }
+}
+
+fn occupied(occupied: bool) {
+    if !occupied {
+        panic!("Attempted to occupy a chained registration more than once.");
+    }
+}
+
+impl<T, R, const N: usize> NvicInterruptRegistration<T> for ChainedRegistration<T, R, N>
+where
+    T: InterruptNumber,
+    R: NvicInterruptRegistration<T>,
+{
+    const INTERRUPT_NUMBER: T = R::INTERRUPT_NUMBER;
+
+    const CORE: Option<u8> = R::CORE;
+
+    fn logical_priority(&self) -> NonZeroU8 {
+        self.priority
+    }
+
+    fn logical_sub_priority(&self) -> u8 {
+        self.sub_priority
+    }
+}
+
+/// An occupied position in a [`HandlerList`].
+///
+/// A [`ChainedOccupation`] is returned by [`ChainedRegistration::attach`].
+pub struct ChainedOccupation<T, R, const N: usize> {
+    registration: ChainedRegistration<T, R, N>,
+}
+
+impl<T, R, const N: usize> ChainedOccupation<T, R, N> {
+    /// The position of this occupation in its [`HandlerList`].
+    pub fn position(&self) -> usize {
+        self.registration.position
+    }
+
+    /// Remove the occupation from the handler list, and return the registration so that it can
+    /// be occupied again.
+    ///
+    /// The occupation is not started by the interrupt after this returns. If `detach` is called
+    /// from a context with a higher priority than the interrupt, the occupation may still be
+    /// running in the preempted interrupt.
+    pub fn detach(self) -> ChainedRegistration<T, R, N> {
+        let registration = self.registration;
+        registration.list.occupations[registration.position].vacate();
+        registration
+    }
+}
+
+/// Chain the [`NvicInterruptRegistration`] `registration` to a [`HandlerList`] with room for
+/// `N` occupations, and return a [`ChainedRegistration`] for each position in the list.
+///
+/// Every time the interrupt fires, the occupations of all positions are run, in order. See
+/// [`HandlerList`].
+///
+/// Every invocation of this macro creates its own `static` [`HandlerList`], so it panics if
+/// it is executed more than once.
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// let registration = cortex_m_interrupt::take_nvic_interrupt!(pac::Interrupt::RCC, 2);
+/// let [clocks, supply] = cortex_m_interrupt::chain_interrupt!(registration, 2);
+///
+/// let clocks = clocks.attach(on_clock_ready);
+/// supply.occupy(on_power_fail);
+///
+/// // Later, `on_clock_ready` is no longer needed.
+/// let clocks = clocks.detach();
+/// ```
+#[macro_export]
+macro_rules! chain_interrupt {
+    ($registration:expr, $n:expr) => {{
+        static LIST: $crate::HandlerList<{ $n }> = $crate::HandlerList::new();
+        LIST.chain($registration, || LIST.dispatch())
+    }};
+}
+
+/// A registration that occupies a [`Handler`] of the test, shared by the tests of
+/// [`HandlerList`] and [`SharedInterrupt`](crate::SharedInterrupt).
+#[cfg(all(test, not(loom)))]
+pub(crate) struct TestRegistration<const NUMBER: u16> {
+    pub parent: &'static Handler,
+    pub priority: u8,
+}
+
+#[cfg(all(test, not(loom)))]
+impl<const NUMBER: u16> InterruptRegistration for TestRegistration<NUMBER> {
+    fn occupy(self, f: fn()) {
+        assert!(self.parent.occupy(f));
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+impl<const NUMBER: u16> NvicInterruptRegistration<crate::nvic::IrqNumber>
+    for TestRegistration<NUMBER>
+{
+    const INTERRUPT_NUMBER: crate::nvic::IrqNumber = crate::nvic::IrqNumber(NUMBER);
+
+    fn logical_priority(&self) -> NonZeroU8 {
+        NonZeroU8::new(self.priority).unwrap()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+#[test]
+fn chain() {
+    use atomic_polyfill::{AtomicU32, Ordering};
+
+    static PARENT: Handler = Handler::new();
+    static RAN: AtomicU32 = AtomicU32::new(0);
+
+    // Every occupation appends its digit, so `RAN` records the order in which they ran.
+    fn push(digit: u32) {
+        let ran = RAN.load(Ordering::Relaxed);
+        RAN.store(ran * 10 + digit, Ordering::Relaxed);
+    }
+
+    let fire = || {
+        RAN.store(0, Ordering::Relaxed);
+        PARENT.get().unwrap()();
+        RAN.load(Ordering::Relaxed)
+    };
+
+    let registration = TestRegistration::<5> {
+        parent: &PARENT,
+        priority: 2,
+    };
+    let [a, b, c] = crate::chain_interrupt!(registration, 3);
+    assert_eq!((a.position(), b.position(), c.position()), (0, 1, 2));
+    assert_eq!(c.number().0, 5);
+    assert_eq!(c.logical_priority().get(), 2);
+
+    assert_eq!(fire(), 0);
+
+    // The order is that of the positions, not that of attaching.
+    let c = c.attach(|| push(3));
+    let a = a.attach(|| push(1));
+    b.occupy(|| push(2));
+    assert_eq!(fire(), 123);
+
+    let a = a.detach();
+    assert_eq!(fire(), 23);
+
+    // Occupations that take the interrupt number are passed that of the registration.
+    let c = c.detach();
+    c.attach_with_number(|irq| push(irq.0 as u32 - 1));
+    a.attach(|| push(5));
+    assert_eq!(fire(), 524);
+}
@@ -0,0 +1,50 @@
+/// Execute `f` in a critical section.
+///
+/// With the `critical-section` feature enabled, this uses [`critical_section::with`], so that the
+/// critical section implementation chosen by the application is respected. Otherwise,
+/// [`Backend::free`] of the [`Active`] backend is used.
+///
+/// When compiled with `--cfg loom`, this locks a `loom` mutex instead, so that the critical
+/// sections of the threads that model thread mode and interrupts exclude each other.
+///
+/// [`Backend::free`]: crate::backend::Backend::free
+/// [`Active`]: crate::backend::Active
+#[inline]
+pub fn with<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(loom)]
+    {
+        extern crate std;
+
+        use core::cell::Cell;
+
+        loom::lazy_static! {
+            static ref LOCK: loom::sync::Mutex<()> = loom::sync::Mutex::new(());
+        }
+
+        loom::thread_local! {
+            static NESTED: Cell<bool> = Cell::new(false);
+        }
+
+        if NESTED.with(Cell::get) {
+            return f();
+        }
+
+        let _guard = LOCK.lock().unwrap();
+        NESTED.with(|nested| nested.set(true));
+        let result = f();
+        NESTED.with(|nested| nested.set(false));
+        result
+    }
+
+    #[cfg(all(not(loom), feature = "critical-section"))]
+    {
+        critical_section::with(|_| f())
+    }
+
+    #[cfg(all(not(loom), not(feature = "critical-section")))]
+    {
+        use crate::backend::{Active, Backend};
+
+        Active::free(f)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::InterruptRegistration;
+use crate::{cortex_m::peripheral::scb::Exception, InterruptRegistration, OccupyWith};
 
 /// A handle that can be used to configure the occupation of an interrupt caused by an exception.
 ///
@@ -14,4 +14,16 @@ pub trait ExceptionRegistration: InterruptRegistration {
     fn exception(&self) -> crate::cortex_m::peripheral::scb::Exception {
         Self::EXCEPTION
     }
+
+    /// Occupy this registration with `f`, which is passed the [`Exception`] of this registration
+    /// every time it runs.
+    ///
+    /// This allows a single function to serve several exceptions. Calling
+    /// `occupy_with_exception` more than once for the same exception will panic.
+    fn occupy_with_exception(self, f: fn(Exception))
+    where
+        Self: Sized + OccupyWith<Exception>,
+    {
+        self.occupy_with(f)
+    }
 }
@@ -0,0 +1,166 @@
+use core::ptr;
+
+use cortex_m::interrupt::InterruptNumber;
+
+use crate::{
+    sync::{AtomicBool, AtomicPtr, Ordering},
+    NvicInterruptRegistration,
+};
+
+/// The occupation of an interrupt, which is published to the interrupt handler through an
+/// atomic pointer.
+///
+/// The occupation is stored with `Release` ordering and loaded with `Acquire` ordering, so the
+/// interrupt handler either observes no occupation at all, or the complete occupation together
+/// with everything that was written before the interrupt was occupied.
+///
+/// An occupation is either a `fn()`, or a `fn(T)` that is passed an argument (such as the
+/// interrupt number) by whoever runs it. Each kind has its own pointer, so the interrupt handler
+/// never observes an occupation together with the wrong kind.
+///
+/// This type is used by the code generated by [`take_nvic_interrupt`] and [`take_exception`],
+/// and is not part of the public API.
+///
+/// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+/// [`take_exception`]: crate::take_exception
+#[doc(hidden)]
+pub struct Handler {
+    occupied: AtomicBool,
+    plain: AtomicPtr<()>,
+    with_argument: AtomicPtr<()>,
+}
+
+impl Handler {
+    /// Create a handler that is not occupied.
+    #[cfg(not(loom))]
+    pub const fn new() -> Self {
+        Self {
+            occupied: AtomicBool::new(false),
+            plain: AtomicPtr::new(ptr::null_mut()),
+            with_argument: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Create a handler that is not occupied.
+    #[cfg(loom)]
+    pub fn new() -> Self {
+        Self {
+            occupied: AtomicBool::new(false),
+            plain: AtomicPtr::new(ptr::null_mut()),
+            with_argument: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Occupy this handler with `f`.
+    ///
+    /// Returns `false` if this handler was already occupied, in which case the occupation
+    /// is not changed.
+    pub fn occupy(&self, f: fn()) -> bool {
+        self.publish(&self.plain, f as *mut ())
+    }
+
+    /// Occupy this handler with `f`, which is passed the argument given to [`Handler::run`].
+    ///
+    /// Returns `false` if this handler was already occupied, in which case the occupation
+    /// is not changed.
+    ///
+    /// # Safety
+    /// [`Handler::run`] must only be called with an argument of type `T` for this handler.
+    pub unsafe fn occupy_with<T>(&self, f: fn(T)) -> bool {
+        self.publish(&self.with_argument, f as *mut ())
+    }
+
+    fn publish(&self, slot: &AtomicPtr<()>, f: *mut ()) -> bool {
+        if self
+            .occupied
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return false;
+        }
+
+        slot.store(f, Ordering::Release);
+        true
+    }
+
+    /// Vacate this handler, so that it can be occupied again.
+    ///
+    /// Returns whether the handler was occupied.
+    pub fn vacate(&self) -> bool {
+        let plain = self.plain.swap(ptr::null_mut(), Ordering::AcqRel);
+        let with_argument = self.with_argument.swap(ptr::null_mut(), Ordering::AcqRel);
+        self.occupied.store(false, Ordering::Release);
+
+        !plain.is_null() || !with_argument.is_null()
+    }
+
+    /// The occupation of this handler, if it is occupied with a `fn()`.
+    #[inline(always)]
+    pub fn get(&self) -> Option<fn()> {
+        let f = self.plain.load(Ordering::Acquire);
+
+        if f.is_null() {
+            None
+        } else {
+            // NOTE(unsafe): the only non-null pointers that are stored in `plain` are `fn()`s.
+            Some(unsafe { core::mem::transmute::<*mut (), fn()>(f) })
+        }
+    }
+
+    /// Whether this handler is occupied.
+    pub fn is_occupied(&self) -> bool {
+        !self.plain.load(Ordering::Acquire).is_null()
+            || !self.with_argument.load(Ordering::Acquire).is_null()
+    }
+
+    /// Run the occupation of this handler, passing it `argument` if it takes one.
+    ///
+    /// Returns `false` if this handler is not occupied.
+    ///
+    /// # Safety
+    /// If this handler was occupied using [`Handler::occupy_with`], `T` must be the type of the
+    /// argument of that occupation.
+    #[inline(always)]
+    pub unsafe fn run<T>(&self, argument: T) -> bool {
+        if let Some(f) = self.get() {
+            f();
+            return true;
+        }
+
+        let f = self.with_argument.load(Ordering::Acquire);
+
+        if f.is_null() {
+            false
+        } else {
+            // NOTE(unsafe): the only non-null pointers that are stored in `with_argument` are
+            // `fn(T)`s, as guaranteed by the caller.
+            core::mem::transmute::<*mut (), fn(T)>(f)(argument);
+            true
+        }
+    }
+
+    /// Run the occupation of this handler, passing it the interrupt number of `R` if it takes
+    /// one.
+    ///
+    /// # Safety
+    /// If this handler was occupied using [`Handler::occupy_with`], `T` must be the type of the
+    /// argument of that occupation.
+    pub(crate) unsafe fn run_nvic<T, R>(&self) -> bool
+    where
+        T: InterruptNumber,
+        R: NvicInterruptRegistration<T>,
+    {
+        self.run(R::INTERRUPT_NUMBER)
+    }
+
+    /// Run nothing, as if this handler was not occupied.
+    pub(crate) unsafe fn run_nothing(&self) -> bool {
+        false
+    }
+}
+
+impl Default for Handler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
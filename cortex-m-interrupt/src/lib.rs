@@ -7,19 +7,42 @@ pub use atomic_polyfill;
 pub use cortex_m;
 pub use cortex_m_rt::DefaultHandler_;
 
+#[doc(hidden)]
+pub mod cs;
+
+mod sync;
+
+mod handler;
+#[doc(hidden)]
+pub use handler::Handler;
+
+pub mod backend;
+
 /// Return an instance of an unnameable struct that implements [`NvicInterruptRegistration`], which
 /// is bound to the interrupt specified by `interrupt` with logical priority `priority`.
 ///
-/// `interrupt` must name an enum variant of an enum that implements [`InterruptNumber`] with _at least_ 2 path segments.
+/// `interrupt` must name an enum variant of an enum that implements [`InterruptNumber`] with _at least_ 2 path segments,
+/// unless the type of the interrupt is specified explicitly.
 ///
 /// For instance, `Interrupt::EXTI15_10` (where `Interrupt` implements [`InterruptNumber`]) is allowed,
-/// but `EXTI15_10` by itself, even if imported using `use Interrupt::EXTI15_10`, is not.
+/// but `EXTI15_10` by itself, even if imported using `use Interrupt::EXTI15_10`, is not. In that case,
+/// the type must be specified as `EXTI15_10: Interrupt`. The explicit type may be any path to the
+/// interrupt enum, including type aliases and re-exports.
+///
+/// ```rust,ignore
+/// use pac::Interrupt::EXTI15_10;
+///
+/// let registration = take_nvic_interrupt!(EXTI15_10: pac::Interrupt, 4);
+/// ```
 ///
 /// The returned struct has the following features:
 /// * Calling `register` more than once for the same `Interrupt` panics.
 /// * The bound interrupt will be masked in the NVIC before configuring the occupation of the registration, and
-/// unmasked after.
-/// * The the amount of available NVIC priority bits is determined runtime.
+///   unmasked after.
+/// * The the amount of available NVIC priority bits is determined runtime, unless it is provided
+///   with `prio_bits` (see [Compile-time priority validation](#compile-time-priority-validation)).
+/// * Taking the same interrupt at more than one place in a program is an error at link time. See
+///   [Duplicate bindings](#duplicate-bindings).
 ///
 /// # Logical priority
 ///
@@ -28,6 +51,129 @@ pub use cortex_m_rt::DefaultHandler_;
 /// has the highest priority level. A logical priority of `0` is not allowed, and a logical priority greater than `2^N` panics
 /// at runtime.
 ///
+/// If the priority grouping (see [`set_prigroup`]) splits the available priority bits into preemption priority
+/// bits and sub-priority bits, `N` is the amount of preemption priority bits. A sub-priority can be specified
+/// with `(priority, sub_priority)`, where the logical sub-priority `0` has the lowest sub-priority level. A logical
+/// sub-priority greater than or equal to `2^S` (where `S = <available sub-priority bits>`) panics at runtime.
+///
+/// ```rust,ignore
+/// let registration = take_nvic_interrupt!(Interrupt::EXTI15_10, (3, 1));
+/// ```
+///
+/// The (preemption) priority can be any constant expression that evaluates to either a `u8` or a
+/// [`Priority`]. A priority of `0` is a compile-time error in both cases.
+///
+/// ```rust,ignore
+/// const PRIO_SENSORS: Priority = Priority::new(3);
+///
+/// let registration = take_nvic_interrupt!(Interrupt::EXTI15_10, PRIO_SENSORS);
+/// ```
+///
+/// # Compile-time priority validation
+///
+/// The amount of priority bits implemented by the NVIC can be provided by adding `prio_bits = <expr>`
+/// after the priority, where `<expr>` is a constant expression such as the `NVIC_PRIO_BITS` constant
+/// of a PAC. In that case:
+/// * a priority and sub-priority that do not fit in `prio_bits` together, however the priority grouping
+///   splits them, are a compile-time error, and
+/// * the amount of priority bits is not determined at runtime, so no interrupt priority is overwritten to
+///   determine it.
+///
+/// ```rust,ignore
+/// let registration = take_nvic_interrupt!(pac::Interrupt::EXTI15_10, 7, prio_bits = pac::NVIC_PRIO_BITS);
+/// ```
+///
+/// The priority grouping is configured at runtime, so only the total width of the priority and sub-priority
+/// is checked at compile time. For instance, with 4 priority bits, `(3, 1)` needs 2 preemption priority bits
+/// and 1 sub-priority bit, which fits. If the priority grouping at runtime does not leave enough bits for
+/// either of them, occupying the registration still panics.
+///
+/// # Options
+///
+/// All arguments after the interrupt can be given as `key = value` options. The priority can be given
+/// either positionally as the second argument, or with `priority = <priority>`. Specifying an option
+/// more than once is a compile-time error.
+///
+/// The following options configure how the interrupt is occupied:
+/// * `enable = <bool>` (default `true`): whether the interrupt is unmasked after it is occupied. With
+///   `enable = false`, the interrupt stays masked until it is unmasked using [`NVIC::unmask`].
+/// * `clear_pending = <bool>` (default `false`): whether the pending state of the interrupt is cleared
+///   before it is unmasked, so that an interrupt that was pended before it was occupied does not fire.
+/// * `ram = <bool>` (default `false`): whether the exported interrupt handler that calls the occupation
+///   is placed in RAM (in a `.data` section) instead of flash. The occupation itself is not moved.
+///
+/// ```rust,ignore
+/// let registration = take_nvic_interrupt!(Interrupt::EXTI1, priority = 4, enable = false, clear_pending = true, ram = true);
+/// ```
+///
+/// # Multi-core
+///
+/// On multi-core MCUs, where each core has its own NVIC, a registration for a specific core can be
+/// taken by adding `core = N` after the priority. Such a registration:
+/// * panics if it is occupied from any core other than core `N`.
+/// * only runs its occupation if the interrupt is serviced by core `N`. If it is serviced by any
+///   other core, the default handler is run instead, as it is for an interrupt that is not
+///   occupied.
+///
+/// An interrupt can only be taken once in the entire program. To occupy the same interrupt on
+/// several cores, take it for each of the first `K` cores at once by adding `cores = K` instead.
+/// This returns a tuple of `K` registrations, where the `n`th registration is the registration for
+/// core `n`. The interrupt handler runs the occupation of the core that services the interrupt,
+/// and the default handler if that core has no occupation.
+///
+/// The application must provide the ID of the current core using [`core_id_impl`].
+///
+/// ```rust,ignore
+/// let registration = take_nvic_interrupt!(Interrupt::TIMER_IRQ_0, 3, core = 1);
+///
+/// let (core0, core1) = take_nvic_interrupt!(Interrupt::TIMER_IRQ_1, 3, cores = 2);
+/// ```
+///
+/// # TrustZone
+///
+/// On ARMv8-M cores with the Security Extension, the Secure image can specify the security state
+/// that the interrupt targets by adding `target = secure` or `target = non_secure` after the priority.
+///
+/// * With `target = secure`, the interrupt is targeted at the Secure state when the registration is
+///   occupied.
+/// * With `target = non_secure`, an implementor of [`NonSecureRegistration`] is returned instead. Its
+///   occupation lives in the Non-secure image, which takes the interrupt without specifying a `target`.
+///   The `enable`, `ram`, `core` and `cores` options configure the occupation, so they can only be
+///   specified in the Non-secure image.
+///
+/// ```rust,ignore
+/// let registration = take_nvic_interrupt!(Interrupt::UARTE0, 2, target = non_secure);
+/// registration.route();
+/// ```
+///
+/// # Duplicate bindings
+///
+/// The handler of a taken interrupt is exported under the name of the interrupt (`EXTI1`), and
+/// next to it, every taken interrupt exports a companion symbol named after the interrupt. If the
+/// same interrupt is taken at more than one place, the linker reports both symbols, together with
+/// the source location of both places (and thereby the crates that they are in). For example,
+/// `rust-lld` reports the following (with the lines naming the object files left out):
+///
+/// ```text
+/// rust-lld: error: duplicate symbol: EXTI1
+/// >>> defined at main.rs:6 (src/main.rs:6)
+/// >>> defined at lib.rs:8 (driver/src/lib.rs:8)
+///
+/// rust-lld: error: duplicate symbol: cortex-m-interrupt: `EXTI1` is taken more than once
+/// >>> defined at main.rs:6 (src/main.rs:6)
+/// >>> defined at lib.rs:8 (driver/src/lib.rs:8)
+/// ```
+///
+/// The `#[interrupt]` attribute of `cortex-m-rt` only exports the interrupt symbol itself, so
+/// there is no companion symbol that it collides with. If an interrupt is taken and also bound
+/// using `#[interrupt]`, the linker only reports the duplicate interrupt symbol:
+///
+/// ```text
+/// rust-lld: error: duplicate symbol: EXTI1
+/// >>> defined at main.rs:4 (src/main.rs:4)
+/// >>> defined at lib.rs:8 (driver/src/lib.rs:8)
+/// ```
+///
 /// # Usage
 ///
 /// ```rust,ignore
@@ -46,6 +192,8 @@ pub use cortex_m_rt::DefaultHandler_;
 /// ```
 ///
 /// [`InterruptNumber`]: cortex_m::interrupt::InterruptNumber
+/// [`core_id_impl`]: crate::core_id_impl
+/// [`NVIC::unmask`]: cortex_m::peripheral::NVIC::unmask
 /// [`Interrupt::EXTI15_10`]: stm32f1xx_hal::pac::interrupt::EXTI15_10
 pub use cortex_m_interrupt_macro::take_nvic_interrupt;
 
@@ -53,10 +201,12 @@ pub use cortex_m_interrupt_macro::take_nvic_interrupt;
 /// is bound to the exception specified by `exception`.
 ///
 /// `exception` may be any of the variants of [`Exception`] (from [`cortex_m`]), except
-/// for [`Exception::HardFault`]
+/// for [`Exception::HardFault`]. On ARMv8-M cores, this includes `SecureFault`.
 ///
 /// The returned struct has the following features:
 /// * Calling `register` more than once for the same [`Exception`] panics.
+/// * Taking the same exception at more than one place in a program is an error at link time, in the same
+///   way as for [`take_nvic_interrupt`] (see [Duplicate bindings](take_nvic_interrupt#duplicate-bindings)).
 ///
 /// # Usage
 ///
@@ -77,11 +227,87 @@ pub use cortex_m_interrupt_macro::take_nvic_interrupt;
 /// [`Exception::HardFault`]: cortex_m::peripheral::scb::Exception::HardFault
 pub use cortex_m_interrupt_macro::take_exception;
 
+/// Turn a function into the occupation of an interrupt.
+///
+/// The arguments of this attribute are the same as those of [`take_nvic_interrupt`], except for
+/// `cores`. The annotated function must have the signature `fn()`.
+///
+/// The annotated function is replaced by a function with the same name that takes the registration
+/// for the interrupt, occupies it with the body of the annotated function, and returns the resulting
+/// [`NvicOccupation`]. The returned [`NvicOccupation`] can be used to read back the priority of the
+/// interrupt, and to mask and unmask it (for instance, when `enable = false` is specified).
+///
+/// As the same mechanism as [`take_nvic_interrupt`] is used, calling the function more than once panics.
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// use cortex_m_interrupt::occupy;
+///
+/// #[occupy(pac::Interrupt::EXTI1, priority = 4, enable = false)]
+/// fn on_exti1() {
+///     // Handle the interrupt.
+/// }
+///
+/// let occupation = on_exti1();
+/// // NOTE(unsafe): the interrupt is not used in a mask-based critical section.
+/// unsafe { occupation.unmask() };
+/// ```
+pub use cortex_m_interrupt_macro::occupy;
+
 mod exception;
 pub use exception::ExceptionRegistration;
 
 mod nvic;
-pub use nvic::{determine_prio_bits, logical2hw, NvicInterruptRegistration};
+pub use nvic::{
+    determine_prio_bits, hw2logical, logical2hw, logical2hw_grouped, prigroup, set_prigroup,
+    NvicInterruptRegistration, NvicOccupation, PrioBits,
+};
+
+mod shared;
+pub use shared::{SharedInterrupt, SharedRegistration};
+
+mod chain;
+pub use chain::{ChainedOccupation, ChainedRegistration, HandlerList};
+
+mod priority;
+#[doc(hidden)]
+pub use priority::PriorityValue;
+pub use priority::{current_logical_priority, logical_priority_of, Priority};
+
+mod monotonic;
+pub use monotonic::{Systick, TickTimer, TimerMonotonic};
+
+mod mutex;
+pub use mutex::PriorityMutex;
+
+mod ssq;
+pub use ssq::{Consumer, Producer, SingleSlotQueue};
+
+mod waker;
+pub use waker::AtomicWaker;
+
+mod ring;
+pub use ring::{Overflow, RingBuffer, RingConsumer, RingProducer};
+
+mod multicore;
+pub use multicore::core_id;
+
+mod trustzone;
+pub use trustzone::{set_target_state, target_state, NonSecureRegistration, TargetState};
+
+#[cfg(all(feature = "mock", not(target_arch = "arm")))]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "mock")))]
+mod mock;
+#[cfg(all(feature = "mock", not(target_arch = "arm")))]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "mock")))]
+pub use mock::{MockException, MockExceptionRegistration, MockInterrupt, MockNvicRegistration};
+
+mod preemption;
+pub use preemption::preemption_point;
+#[cfg(all(feature = "mock", not(target_arch = "arm")))]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "mock")))]
+pub use preemption::{Failure, Injection, Preemption};
 
 /// An interrupt registration, whose occupation can be configured.
 ///
@@ -93,3 +319,22 @@ pub trait InterruptRegistration {
     /// Calling `register` more than once for the same interrupt will panic.
     fn occupy(self, f: fn());
 }
+
+/// An [`InterruptRegistration`] whose occupation can take an argument of type `T`, which it is
+/// passed every time it runs.
+///
+/// The registrations created by [`take_nvic_interrupt`] pass their interrupt number, and those
+/// created by [`take_exception`] pass their [`Exception`]. Use
+/// [`NvicInterruptRegistration::occupy_with_number`] or
+/// [`ExceptionRegistration::occupy_with_exception`] to occupy them.
+///
+/// Implementing this trait is optional: registrations that do not implement it can only be
+/// occupied with a `fn()`.
+///
+/// [`Exception`]: cortex_m::peripheral::scb::Exception
+pub trait OccupyWith<T>: InterruptRegistration {
+    /// Occupy this registration with `f`, which is passed an argument every time it runs.
+    ///
+    /// Calling `occupy_with` more than once for the same interrupt will panic.
+    fn occupy_with(self, f: fn(T));
+}
@@ -0,0 +1,317 @@
+use core::{marker::PhantomData, num::NonZeroU8};
+
+use cortex_m::{interrupt::InterruptNumber, peripheral::scb::Exception};
+
+use crate::{
+    backend::{Active, Backend, Simulated},
+    ExceptionRegistration, Handler, InterruptRegistration, NvicInterruptRegistration, OccupyWith,
+    PrioBits, Priority,
+};
+
+/// An interrupt that a [`MockNvicRegistration`] is bound to.
+///
+/// As [`NvicInterruptRegistration::INTERRUPT_NUMBER`] is an associated constant, the interrupt
+/// of a mock registration is selected using a type that implements this trait.
+///
+/// ```rust,ignore
+/// struct Exti1;
+///
+/// impl MockInterrupt for Exti1 {
+///     type Interrupt = pac::Interrupt;
+///     const INTERRUPT: pac::Interrupt = pac::Interrupt::EXTI1;
+/// }
+/// ```
+pub trait MockInterrupt {
+    /// The type of the interrupt.
+    type Interrupt: InterruptNumber;
+    /// The interrupt itself.
+    const INTERRUPT: Self::Interrupt;
+}
+
+/// An exception that a [`MockExceptionRegistration`] is bound to.
+///
+/// As [`ExceptionRegistration::EXCEPTION`] is an associated constant, the exception of a
+/// mock registration is selected using a type that implements this trait.
+pub trait MockException {
+    /// The exception itself.
+    const EXCEPTION: Exception;
+}
+
+/// A mock [`NvicInterruptRegistration`], for testing drivers on the host.
+///
+/// The registration traits are implemented for `&MockNvicRegistration`, so that the mock
+/// can still be used after a driver has occupied it. Occupying it configures the interrupt
+/// in the [`Simulated`] NVIC in the same way as [`take_nvic_interrupt`] does, and captures
+/// the occupation so that it can be run using [`MockNvicRegistration::fire`].
+///
+/// Unlike [`take_nvic_interrupt`], any amount of mocks can be created for the same interrupt.
+/// As they share the [`Simulated`] NVIC, tests that use mocks of the same interrupt should not
+/// run concurrently.
+///
+/// ```rust,ignore
+/// let exti1 = MockNvicRegistration::<Exti1>::new(Priority::new(3));
+/// let pin = AsyncExtiPin::new(pin, &exti1);
+///
+/// exti1.fire();
+/// assert!(exti1.is_enabled());
+/// ```
+///
+/// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+pub struct MockNvicRegistration<M: MockInterrupt> {
+    priority: Priority,
+    sub_priority: u8,
+    occupation: Handler,
+    _interrupt: PhantomData<M>,
+}
+
+impl<M: MockInterrupt> MockNvicRegistration<M> {
+    /// Create a new mock registration with logical priority `priority`.
+    pub const fn new(priority: Priority) -> Self {
+        Self {
+            priority,
+            sub_priority: 0,
+            occupation: Handler::new(),
+            _interrupt: PhantomData,
+        }
+    }
+
+    /// Use logical sub-priority `sub_priority` for this mock registration.
+    pub const fn with_sub_priority(mut self, sub_priority: u8) -> Self {
+        self.sub_priority = sub_priority;
+        self
+    }
+
+    /// Whether this mock registration has been occupied.
+    pub fn is_occupied(&self) -> bool {
+        self.occupation.is_occupied()
+    }
+
+    /// Pend the interrupt, and run the captured occupation if the interrupt is enabled.
+    ///
+    /// Returns whether the occupation was run. If it was not, the interrupt stays pending
+    /// until it is run using [`MockNvicRegistration::run_pending`].
+    pub fn fire(&self) -> bool {
+        Active::pend(M::INTERRUPT);
+        self.run_pending()
+    }
+
+    /// Run the captured occupation if the interrupt is pending and enabled, and clear its
+    /// pending state.
+    ///
+    /// Returns whether the occupation was run.
+    pub fn run_pending(&self) -> bool {
+        if self.is_occupied() && self.is_pending() && self.is_enabled() {
+            Active::unpend(M::INTERRUPT);
+            // NOTE(unsafe): occupations that take an argument are passed the interrupt number.
+            unsafe { self.occupation.run(M::INTERRUPT) }
+        } else {
+            false
+        }
+    }
+
+    /// Whether the interrupt is enabled (unmasked) in the [`Simulated`] NVIC.
+    pub fn is_enabled(&self) -> bool {
+        Active::is_enabled(M::INTERRUPT)
+    }
+
+    /// Whether the interrupt is pending in the [`Simulated`] NVIC.
+    pub fn is_pending(&self) -> bool {
+        Active::is_pending(M::INTERRUPT)
+    }
+
+    /// The logical priority that the interrupt is configured with in the [`Simulated`] NVIC.
+    pub fn priority(&self) -> Option<Priority> {
+        Priority::from_hw(Active::priority(M::INTERRUPT), prio_bits().preempt)
+    }
+
+    /// Configure the interrupt in the same way as [`take_nvic_interrupt`] does, around `occupy`.
+    ///
+    /// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+    fn configure(&self, occupy: impl FnOnce(&Handler) -> bool) {
+        crate::cs::with(|| {
+            Active::mask(M::INTERRUPT);
+
+            occupied(occupy(&self.occupation));
+
+            let hw =
+                crate::logical2hw_grouped(self.priority.logical(), self.sub_priority, prio_bits())
+                    .expect("Unsupported priority level.");
+
+            // NOTE(unsafe): the simulated NVIC is not used for mask-based critical sections.
+            unsafe {
+                Active::set_priority(M::INTERRUPT, hw);
+                Active::unmask(M::INTERRUPT);
+            }
+        })
+    }
+}
+
+impl<M: MockInterrupt> InterruptRegistration for &MockNvicRegistration<M> {
+    fn occupy(self, f: fn()) {
+        self.configure(|handler| handler.occupy(f));
+    }
+}
+
+impl<M: MockInterrupt> OccupyWith<M::Interrupt> for &MockNvicRegistration<M> {
+    fn occupy_with(self, f: fn(M::Interrupt)) {
+        // NOTE(unsafe): `run_pending` passes `M::INTERRUPT`.
+        self.configure(|handler| unsafe { handler.occupy_with(f) });
+    }
+}
+
+impl<M: MockInterrupt> NvicInterruptRegistration<M::Interrupt> for &MockNvicRegistration<M> {
+    const INTERRUPT_NUMBER: M::Interrupt = M::INTERRUPT;
+
+    fn logical_priority(&self) -> NonZeroU8 {
+        self.priority.logical()
+    }
+
+    fn logical_sub_priority(&self) -> u8 {
+        self.sub_priority
+    }
+}
+
+/// A mock [`ExceptionRegistration`], for testing drivers on the host.
+///
+/// The registration traits are implemented for `&MockExceptionRegistration`, so that the
+/// mock can still be used after a driver has occupied it.
+pub struct MockExceptionRegistration<E: MockException> {
+    occupation: Handler,
+    _exception: PhantomData<E>,
+}
+
+impl<E: MockException> MockExceptionRegistration<E> {
+    /// Create a new mock registration.
+    pub const fn new() -> Self {
+        Self {
+            occupation: Handler::new(),
+            _exception: PhantomData,
+        }
+    }
+
+    /// Whether this mock registration has been occupied.
+    pub fn is_occupied(&self) -> bool {
+        self.occupation.is_occupied()
+    }
+
+    /// Run the captured occupation, as if the exception was raised.
+    ///
+    /// Returns whether the occupation was run, i.e. whether the mock was occupied.
+    pub fn fire(&self) -> bool {
+        // NOTE(unsafe): occupations that take an argument are passed the exception.
+        unsafe { self.occupation.run(E::EXCEPTION) }
+    }
+
+    /// The logical priority of the exception in the [`Simulated`] SCB, or `None` if the
+    /// exception has a fixed priority.
+    pub fn priority(&self) -> Option<Priority> {
+        let number = (16 + E::EXCEPTION.irqn() as i16) as u8;
+        if number < 4 {
+            return None;
+        }
+
+        Priority::from_hw(Active::system_handler_priority(number), prio_bits().preempt)
+    }
+}
+
+impl<E: MockException> Default for MockExceptionRegistration<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: MockException> InterruptRegistration for &MockExceptionRegistration<E> {
+    fn occupy(self, f: fn()) {
+        occupied(self.occupation.occupy(f));
+    }
+}
+
+impl<E: MockException> OccupyWith<Exception> for &MockExceptionRegistration<E> {
+    fn occupy_with(self, f: fn(Exception)) {
+        // NOTE(unsafe): `fire` passes `E::EXCEPTION`.
+        occupied(unsafe { self.occupation.occupy_with(f) });
+    }
+}
+
+impl<E: MockException> ExceptionRegistration for &MockExceptionRegistration<E> {
+    const EXCEPTION: Exception = E::EXCEPTION;
+}
+
+fn occupied(occupied: bool) {
+    if !occupied {
+        panic!("Attempted to occupy a mock registration more than once.");
+    }
+}
+
+fn prio_bits() -> PrioBits {
+    PrioBits::new(Simulated::implemented_bits(), Active::prigroup())
+}
+
+#[cfg(test)]
+#[test]
+fn mock() {
+    use atomic_polyfill::{AtomicU8, Ordering};
+
+    use crate::nvic::IrqNumber;
+
+    struct Irq100;
+
+    impl MockInterrupt for Irq100 {
+        type Interrupt = IrqNumber;
+        const INTERRUPT: IrqNumber = IrqNumber(100);
+    }
+
+    struct Irq101;
+
+    impl MockInterrupt for Irq101 {
+        type Interrupt = IrqNumber;
+        const INTERRUPT: IrqNumber = IrqNumber(101);
+    }
+
+    struct SysTick;
+
+    impl MockException for SysTick {
+        const EXCEPTION: Exception = Exception::SysTick;
+    }
+
+    static FIRED: AtomicU8 = AtomicU8::new(0);
+
+    let irq = MockNvicRegistration::<Irq100>::new(Priority::new(3));
+    assert!(!irq.fire());
+
+    let occupation = (&irq).occupy_nvic(|| {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    });
+    assert!(irq.is_enabled());
+    assert_eq!(irq.priority(), Some(Priority::new(3)));
+
+    // The interrupt was pended before it was occupied.
+    assert!(irq.run_pending());
+    assert!(irq.fire());
+    assert_eq!(FIRED.load(Ordering::Relaxed), 2);
+
+    occupation.masked(|| {
+        assert!(!irq.fire());
+        assert!(irq.is_pending());
+    });
+    assert!(irq.run_pending());
+    assert_eq!(FIRED.load(Ordering::Relaxed), 3);
+
+    let systick = MockExceptionRegistration::<SysTick>::new();
+    assert!(!systick.fire());
+
+    (&systick).occupy(|| {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    });
+    assert!(systick.fire());
+    assert_eq!(FIRED.load(Ordering::Relaxed), 4);
+    assert_eq!(systick.priority(), Priority::from_hw(0, 4));
+
+    // Occupations that take the interrupt number are passed that of the mock.
+    let irq = MockNvicRegistration::<Irq101>::new(Priority::new(1));
+    (&irq).occupy_with_number(|irq| {
+        FIRED.store(irq.0 as u8, Ordering::Relaxed);
+    });
+    assert!(irq.fire());
+    assert_eq!(FIRED.load(Ordering::Relaxed), 101);
+}
@@ -0,0 +1,495 @@
+use atomic_polyfill::{AtomicBool, Ordering};
+use cortex_m::{
+    interrupt::InterruptNumber,
+    peripheral::{scb::Exception, syst::SystClkSource, SYST},
+};
+
+use crate::{ExceptionRegistration, NvicInterruptRegistration};
+
+/// The amount of ticks that have elapsed since the monotonic timer was created.
+///
+/// This value is only written from the occupation of the monotonic timer.
+static mut TICKS: u64 = 0;
+
+/// Whether a monotonic timer has been created.
+///
+/// The ticks, the RTIC compare value and the `embassy-time` alarms are shared by all monotonic
+/// timers, so only one of them can exist.
+static CREATED: AtomicBool = AtomicBool::new(false);
+
+/// Claim the state of the monotonic timer, and reset its ticks.
+///
+/// # Panics
+/// If a monotonic timer has been created before.
+fn claim() {
+    if CREATED.swap(true, Ordering::AcqRel) {
+        panic!("Attempted to create more than one monotonic timer.");
+    }
+
+    // NOTE(unsafe): the occupation that writes `TICKS` has not been occupied yet.
+    crate::cs::with(|| unsafe { TICKS = 0 });
+}
+
+/// A monotonic timer, counting at `TIMER_HZ` ticks per second, that
+/// occupies the `SysTick` exception.
+///
+/// A [`Systick`] is created from an [`ExceptionRegistration`] for [`Exception::SysTick`]. To use a
+/// hardware timer instead, see [`TimerMonotonic`]. Only one of them can be created.
+///
+/// With the `rtic-monotonic` feature enabled, [`Systick`] implements [`rtic_monotonic::Monotonic`].
+/// With the `embassy-time` feature enabled, [`Systick`] provides the `embassy-time` driver, in which
+/// case `TIMER_HZ` must be equal to the configured `embassy-time` tick rate. This is checked at
+/// compile time.
+///
+/// [`Exception::SysTick`]: cortex_m::peripheral::scb::Exception::SysTick
+pub struct Systick<const TIMER_HZ: u32> {
+    _syst: SYST,
+}
+
+impl<const TIMER_HZ: u32> Systick<TIMER_HZ> {
+    /// Create a new [`Systick`], which occupies `registration` and configures `syst`
+    /// to tick at `TIMER_HZ`, given that the core runs at `sysclk` Hz.
+    ///
+    /// # Panics
+    /// This function panics if `registration` is not associated with [`Exception::SysTick`],
+    /// if `sysclk / TIMER_HZ` does not fit in the 24-bit reload register, or if a monotonic timer
+    /// has been created before.
+    ///
+    /// With the `embassy-time` feature enabled, using a `TIMER_HZ` that is not equal to the
+    /// configured `embassy-time` tick rate is a compile-time error.
+    ///
+    /// [`Exception::SysTick`]: cortex_m::peripheral::scb::Exception::SysTick
+    pub fn new<R: ExceptionRegistration>(registration: R, mut syst: SYST, sysclk: u32) -> Self {
+        assert_eq!(registration.exception(), Exception::SysTick);
+
+        #[cfg(feature = "embassy-time")]
+        embassy::check_tick_rate::<TIMER_HZ>();
+
+        let reload = reload_value(sysclk, TIMER_HZ).expect("Unsupported SysTick tick rate");
+
+        // Claim the monotonic timer before touching the SysTick, so that a second monotonic timer
+        // does not reprogram it.
+        claim();
+
+        syst.disable_counter();
+        syst.disable_interrupt();
+
+        registration.occupy(on_tick);
+
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(reload);
+        syst.clear_current();
+        syst.enable_interrupt();
+        syst.enable_counter();
+
+        Self { _syst: syst }
+    }
+
+    /// The amount of ticks that have elapsed since this [`Systick`] was created.
+    pub fn now() -> u64 {
+        ticks()
+    }
+}
+
+fn ticks() -> u64 {
+    // NOTE(unsafe): `TICKS` is only written from the SysTick occupation,
+    // which can not preempt us inside of the critical section.
+    crate::cs::with(|| unsafe { TICKS })
+}
+
+/// Calculate the SysTick reload value required to tick at `timer_hz` with a
+/// core clock of `sysclk`.
+///
+/// `None` is returned if the resulting reload value does not fit in the
+/// 24 bits of the reload register, or is zero.
+fn reload_value(sysclk: u32, timer_hz: u32) -> Option<u32> {
+    let reload = sysclk.checked_div(timer_hz)?.checked_sub(1)?;
+
+    if reload > 0 && reload <= 0x00FF_FFFF {
+        Some(reload)
+    } else {
+        None
+    }
+}
+
+/// A hardware timer that interrupts at a fixed rate, which a [`TimerMonotonic`] counts the
+/// interrupts of.
+///
+/// ```rust,ignore
+/// // For example, using stm32f1xx hal:
+/// struct Tim2(pac::TIM2);
+///
+/// impl TickTimer for Tim2 {
+///     fn start(&mut self, timer_hz: u32) {
+///         let arr = SYSCLK / timer_hz - 1;
+///         self.0.arr.write(|w| w.arr().bits(arr as u16));
+///         self.0.dier.write(|w| w.uie().set_bit());
+///         self.0.cr1.write(|w| w.cen().set_bit());
+///     }
+///
+///     fn clear_interrupt() {
+///         let tim2 = unsafe { &*pac::TIM2::ptr() };
+///         tim2.sr.write(|w| w.uif().clear_bit());
+///     }
+/// }
+/// ```
+pub trait TickTimer {
+    /// Configure the timer to interrupt `timer_hz` times per second, and start it.
+    ///
+    /// This should panic if the timer does not support `timer_hz`.
+    fn start(&mut self, timer_hz: u32);
+
+    /// Clear the interrupt flag of the timer.
+    ///
+    /// This is called at the start of every interrupt of the timer. As the occupation of an
+    /// interrupt can not capture any state, it does not take `self`.
+    fn clear_interrupt();
+}
+
+/// A monotonic timer, counting at `TIMER_HZ` ticks per second, that occupies the interrupt of
+/// a hardware timer.
+///
+/// A [`TimerMonotonic`] is created from a [`TickTimer`] and the [`NvicInterruptRegistration`] of
+/// its interrupt. Apart from that, it behaves the same as a [`Systick`]: it implements
+/// [`rtic_monotonic::Monotonic`] with the `rtic-monotonic` feature, and provides the
+/// `embassy-time` driver with the `embassy-time` feature. Only one of them can be created.
+pub struct TimerMonotonic<T: TickTimer, const TIMER_HZ: u32> {
+    _timer: T,
+}
+
+impl<T: TickTimer, const TIMER_HZ: u32> TimerMonotonic<T, TIMER_HZ> {
+    /// Create a new [`TimerMonotonic`], which occupies `registration` and starts `timer` at
+    /// `TIMER_HZ`.
+    ///
+    /// `registration` must be the registration of the interrupt of `timer`.
+    ///
+    /// # Panics
+    /// This function panics if a monotonic timer has been created before, or if `timer`
+    /// panics when it is started.
+    ///
+    /// With the `embassy-time` feature enabled, using a `TIMER_HZ` that is not equal to the
+    /// configured `embassy-time` tick rate is a compile-time error.
+    pub fn new<I, R>(registration: R, mut timer: T) -> Self
+    where
+        I: InterruptNumber,
+        R: NvicInterruptRegistration<I>,
+    {
+        #[cfg(feature = "embassy-time")]
+        embassy::check_tick_rate::<TIMER_HZ>();
+
+        claim();
+
+        registration.occupy(on_timer::<T>);
+
+        timer.start(TIMER_HZ);
+
+        Self { _timer: timer }
+    }
+
+    /// The amount of ticks that have elapsed since this [`TimerMonotonic`] was created.
+    pub fn now() -> u64 {
+        ticks()
+    }
+}
+
+/// The occupation of the interrupt of the [`TickTimer`] `T`.
+fn on_timer<T: TickTimer>() {
+    T::clear_interrupt();
+    on_tick();
+}
+
+/// The occupation of the `SysTick` exception, and the tick of every other monotonic timer.
+fn on_tick() {
+    // NOTE(unsafe): this is the only place where `TICKS` is written to after the monotonic timer
+    // was created, and the interrupt of the monotonic timer can not preempt itself.
+    let _now = unsafe {
+        TICKS = TICKS.wrapping_add(1);
+        TICKS
+    };
+
+    #[cfg(feature = "rtic-monotonic")]
+    rtic::on_tick(_now);
+
+    #[cfg(feature = "embassy-time")]
+    embassy::on_tick(_now);
+}
+
+#[cfg(feature = "rtic-monotonic")]
+mod rtic {
+    use atomic_polyfill::{AtomicU16, Ordering};
+    use cortex_m::interrupt::InterruptNumber;
+    use rtic_monotonic::Monotonic;
+
+    use super::{Systick, TickTimer, TimerMonotonic, TICKS};
+    use crate::{
+        backend::{Active, Backend},
+        nvic::IrqNumber,
+    };
+
+    /// The instant (in ticks) at which the RTIC timer queue should be serviced,
+    /// or `u64::MAX` if no compare is set.
+    ///
+    /// Only accessed from within a critical section.
+    static mut COMPARE: u64 = u64::MAX;
+
+    /// The interrupt that RTIC bound to the monotonic, or `u16::MAX`
+    /// if no interrupt is bound.
+    static BINDS: AtomicU16 = AtomicU16::new(u16::MAX);
+
+    pub(super) fn on_tick(now: u64) {
+        let compare = crate::cs::with(|| unsafe { COMPARE });
+        let binds = BINDS.load(Ordering::Relaxed);
+
+        if now >= compare && binds != u16::MAX {
+            Active::pend(IrqNumber(binds));
+        }
+    }
+
+    /// Implement `bind_rtic` and [`Monotonic`] for a monotonic timer, given as the generic
+    /// parameters before `TIMER_HZ` (with a trailing comma) and the type.
+    macro_rules! monotonic {
+        ([$($generics:tt)*] $monotonic:ty) => {
+            #[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "rtic-monotonic")))]
+            impl<$($generics)* const TIMER_HZ: u32> $monotonic {
+                /// Bind this monotonic timer to the interrupt that RTIC services the timer queue
+                /// of this monotonic in (the `binds` argument of `#[monotonic]`).
+                ///
+                /// The interrupt of the monotonic timer is occupied by the monotonic timer itself,
+                /// so RTIC must be bound to an otherwise unused interrupt, which is pended whenever
+                /// the compare instant is reached.
+                pub fn bind_rtic<I: InterruptNumber>(self, binds: I) -> Self {
+                    BINDS.store(binds.number(), Ordering::Relaxed);
+                    self
+                }
+            }
+
+            #[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "rtic-monotonic")))]
+            impl<$($generics)* const TIMER_HZ: u32> Monotonic for $monotonic {
+                const DISABLE_INTERRUPT_ON_EMPTY_QUEUE: bool = false;
+
+                type Instant = fugit::TimerInstantU64<TIMER_HZ>;
+                type Duration = fugit::TimerDurationU64<TIMER_HZ>;
+
+                fn now(&mut self) -> Self::Instant {
+                    Self::Instant::from_ticks(super::ticks())
+                }
+
+                fn set_compare(&mut self, instant: Self::Instant) {
+                    crate::cs::with(|| unsafe { COMPARE = instant.ticks() });
+                }
+
+                fn clear_compare_flag(&mut self) {
+                    crate::cs::with(|| unsafe { COMPARE = u64::MAX });
+                }
+
+                fn zero() -> Self::Instant {
+                    Self::Instant::from_ticks(0)
+                }
+
+                unsafe fn reset(&mut self) {
+                    crate::cs::with(|| {
+                        TICKS = 0;
+                        COMPARE = u64::MAX;
+                    });
+                }
+            }
+        };
+    }
+
+    monotonic!([] Systick<TIMER_HZ>);
+    monotonic!([T: TickTimer,] TimerMonotonic<T, TIMER_HZ>);
+}
+
+#[cfg(feature = "embassy-time")]
+mod embassy {
+    use core::cell::Cell;
+
+    use atomic_polyfill::{AtomicU8, Ordering};
+    use embassy_time_driver::{AlarmHandle, Driver};
+
+    /// The amount of alarms that the `embassy-time` driver provides.
+    const ALARM_COUNT: usize = 4;
+
+    #[derive(Clone, Copy)]
+    struct Alarm {
+        timestamp: u64,
+        callback: Option<fn(*mut ())>,
+        ctx: *mut (),
+    }
+
+    impl Alarm {
+        const fn new() -> Self {
+            Self {
+                timestamp: u64::MAX,
+                callback: None,
+                ctx: core::ptr::null_mut(),
+            }
+        }
+    }
+
+    /// The alarms of the `embassy-time` driver.
+    ///
+    /// The alarms may only be accessed from within a critical section.
+    struct Alarms([Cell<Alarm>; ALARM_COUNT]);
+
+    // NOTE(unsafe): the alarms are only accessed from within a critical section, and
+    // each context pointer is only handed back to the callback it was registered with.
+    unsafe impl Sync for Alarms {}
+
+    static ALARMS: Alarms = Alarms([
+        Cell::new(Alarm::new()),
+        Cell::new(Alarm::new()),
+        Cell::new(Alarm::new()),
+        Cell::new(Alarm::new()),
+    ]);
+    static ALLOCATED: AtomicU8 = AtomicU8::new(0);
+
+    struct MonotonicDriver;
+
+    /// Check that a monotonic timer that ticks at `TIMER_HZ` can provide the `embassy-time` driver.
+    pub(super) fn check_tick_rate<const TIMER_HZ: u32>() {
+        const {
+            assert!(
+                TIMER_HZ as u64 == embassy_time_driver::TICK_HZ,
+                "The tick rate of the monotonic timer must be equal to the `embassy-time` tick rate."
+            )
+        }
+    }
+
+    impl Driver for MonotonicDriver {
+        fn now(&self) -> u64 {
+            super::ticks()
+        }
+
+        unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+            let id = ALLOCATED.fetch_update(Ordering::AcqRel, Ordering::Acquire, |id| {
+                if (id as usize) < ALARM_COUNT {
+                    Some(id + 1)
+                } else {
+                    None
+                }
+            });
+
+            id.ok().map(|id| AlarmHandle::new(id))
+        }
+
+        fn set_alarm_callback(&self, alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+            crate::cs::with(|| {
+                let alarm = &ALARMS.0[alarm.id() as usize];
+                alarm.set(Alarm {
+                    callback: Some(callback),
+                    ctx,
+                    ..alarm.get()
+                });
+            })
+        }
+
+        fn set_alarm(&self, alarm: AlarmHandle, timestamp: u64) -> bool {
+            crate::cs::with(|| {
+                let alarm = &ALARMS.0[alarm.id() as usize];
+
+                if timestamp <= self.now() {
+                    alarm.set(Alarm {
+                        timestamp: u64::MAX,
+                        ..alarm.get()
+                    });
+                    false
+                } else {
+                    alarm.set(Alarm {
+                        timestamp,
+                        ..alarm.get()
+                    });
+                    true
+                }
+            })
+        }
+    }
+
+    embassy_time_driver::time_driver_impl!(static DRIVER: MonotonicDriver = MonotonicDriver);
+
+    pub(super) fn on_tick(now: u64) {
+        for id in 0..ALARM_COUNT {
+            let callback = crate::cs::with(|| {
+                let alarm = &ALARMS.0[id];
+                let current = alarm.get();
+
+                if current.timestamp <= now {
+                    alarm.set(Alarm {
+                        timestamp: u64::MAX,
+                        ..current
+                    });
+                    current.callback.map(|callback| (callback, current.ctx))
+                } else {
+                    None
+                }
+            });
+
+            if let Some((callback, ctx)) = callback {
+                callback(ctx);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn reload() {
+    assert_eq!(reload_value(72_000_000, 1_000), Some(71_999));
+    assert_eq!(reload_value(72_000_000, 1), None);
+    assert_eq!(reload_value(1_000, 1_000), None);
+    assert_eq!(reload_value(1_000, 0), None);
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[test]
+fn timer_monotonic() {
+    use atomic_polyfill::AtomicU32;
+
+    use crate::{nvic::IrqNumber, MockInterrupt, MockNvicRegistration, Priority};
+
+    static STARTED_AT: AtomicU32 = AtomicU32::new(0);
+    static CLEARED: AtomicU32 = AtomicU32::new(0);
+
+    struct Timer;
+
+    impl TickTimer for Timer {
+        fn start(&mut self, timer_hz: u32) {
+            STARTED_AT.store(timer_hz, Ordering::Relaxed);
+        }
+
+        fn clear_interrupt() {
+            CLEARED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    struct Irq102;
+
+    impl MockInterrupt for Irq102 {
+        type Interrupt = IrqNumber;
+        const INTERRUPT: IrqNumber = IrqNumber(102);
+    }
+
+    static TIMER: MockNvicRegistration<Irq102> = MockNvicRegistration::new(Priority::new(1));
+
+    // With the `embassy-time` feature, the tick rate must be the `embassy-time` tick rate.
+    #[cfg(feature = "embassy-time")]
+    const TIMER_HZ: u32 = embassy_time_driver::TICK_HZ as u32;
+    #[cfg(not(feature = "embassy-time"))]
+    const TIMER_HZ: u32 = 1_000;
+
+    type Monotonic = TimerMonotonic<Timer, TIMER_HZ>;
+
+    let _monotonic = Monotonic::new(&TIMER, Timer);
+    assert_eq!(STARTED_AT.load(Ordering::Relaxed), TIMER_HZ);
+    assert_eq!(Monotonic::now(), 0);
+
+    for _ in 0..3 {
+        assert!(TIMER.fire());
+    }
+    assert_eq!(Monotonic::now(), 3);
+    assert_eq!(CLEARED.load(Ordering::Relaxed), 3);
+
+    // The ticks are shared by all monotonic timers, so only one can be created.
+    let second = std::panic::catch_unwind(|| Monotonic::new(&TIMER, Timer));
+    assert!(second.is_err());
+}
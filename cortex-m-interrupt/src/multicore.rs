@@ -0,0 +1,42 @@
+extern "Rust" {
+    fn _cortex_m_interrupt_core_id() -> u8;
+}
+
+/// The ID of the core that is currently executing.
+///
+/// The ID of the current core is determined by the function provided with [`core_id_impl`],
+/// which must be provided if a registration for a specific core is taken using
+/// [`take_nvic_interrupt`].
+///
+/// [`core_id_impl`]: crate::core_id_impl
+/// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+pub fn core_id() -> u8 {
+    // NOTE(unsafe): the symbol is provided by `core_id_impl`, and has
+    // the correct signature.
+    unsafe { _cortex_m_interrupt_core_id() }
+}
+
+/// Provide the function that [`core_id`] uses to determine the ID of the current core.
+///
+/// This macro must be invoked exactly once in the final program if a registration for a
+/// specific core is taken using [`take_nvic_interrupt`].
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// // For example, on the RP2040:
+/// cortex_m_interrupt::core_id_impl!(|| rp2040_hal::Sio::core());
+/// ```
+///
+/// [`core_id`]: crate::core_id
+/// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+#[macro_export]
+macro_rules! core_id_impl {
+    ($core_id:expr) => {
+        #[unsafe(no_mangle)]
+        fn _cortex_m_interrupt_core_id() -> u8 {
+            let core_id: fn() -> u8 = $core_id;
+            core_id()
+        }
+    };
+}
@@ -0,0 +1,230 @@
+use core::{cell::UnsafeCell, num::NonZeroU8};
+
+use atomic_polyfill::{AtomicBool, AtomicU8, Ordering};
+use cortex_m::interrupt::InterruptNumber;
+
+use crate::NvicInterruptRegistration;
+
+/// A mutex that protects data that is shared between thread mode and the occupations
+/// of one or more [`NvicInterruptRegistration`]s, using the priority ceiling protocol.
+///
+/// The ceiling of a [`PriorityMutex`] is the highest logical priority of its users, which
+/// are added using [`PriorityMutex::add_user`]. Locking the mutex raises `BASEPRI` to the
+/// ceiling, so that occupations with a priority higher than the ceiling can still preempt
+/// the code holding the lock.
+///
+/// On cores that do not implement `BASEPRI` (ARMv6-M and ARMv8-M Baseline), and when locking
+/// from a system exception, a global critical section is used instead.
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// use cortex_m_interrupt::{take_nvic_interrupt, InterruptRegistration, PriorityMutex};
+///
+/// static COUNTER: PriorityMutex<u32> = PriorityMutex::new(0, pac::NVIC_PRIO_BITS);
+///
+/// let registration = take_nvic_interrupt!(pac::Interrupt::EXTI1, 4);
+///
+/// COUNTER.add_user(&registration);
+/// registration.occupy(|| COUNTER.lock(|counter| *counter += 1));
+///
+/// let count = COUNTER.lock(|counter| *counter);
+/// ```
+pub struct PriorityMutex<T> {
+    #[cfg_attr(not(has_basepri), allow(dead_code))]
+    nvic_prio_bits: u8,
+    ceiling: AtomicU8,
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+// NOTE(unsafe): access to `data` is only given out from within `lock`, which
+// guarantees exclusive access.
+unsafe impl<T: Send> Sync for PriorityMutex<T> {}
+
+impl<T> PriorityMutex<T> {
+    /// Create a new [`PriorityMutex`] without any users, protecting `data`.
+    ///
+    /// `nvic_prio_bits` must be the amount of preemption priority bits that the NVIC of the
+    /// current MCU implements (see [`PrioBits`]).
+    ///
+    /// [`PrioBits`]: crate::PrioBits
+    pub const fn new(data: T, nvic_prio_bits: u8) -> Self {
+        Self {
+            nvic_prio_bits,
+            ceiling: AtomicU8::new(0),
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Add the occupation of `registration` as a user of this mutex.
+    ///
+    /// This raises the ceiling of this mutex to the logical priority of `registration`, if
+    /// it is higher than the current ceiling.
+    pub fn add_user<I: InterruptNumber, R: NvicInterruptRegistration<I>>(&self, registration: &R) {
+        self.raise_ceiling(registration.logical_priority());
+    }
+
+    /// Raise the ceiling of this mutex to the logical priority `logical`, if it is higher
+    /// than the current ceiling.
+    pub fn raise_ceiling(&self, logical: NonZeroU8) {
+        self.ceiling.fetch_max(logical.get(), Ordering::AcqRel);
+    }
+
+    /// The logical priority ceiling of this mutex, or `None` if it has no users.
+    pub fn ceiling(&self) -> Option<NonZeroU8> {
+        NonZeroU8::new(self.ceiling.load(Ordering::Acquire))
+    }
+
+    /// Lock this mutex, and call `f` with exclusive access to the protected data.
+    ///
+    /// # Panics
+    /// This function panics if it is called from an interrupt with a priority higher than
+    /// the ceiling of this mutex, or if it is called from within `f`.
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.critical_section(|| {
+            if self.locked.swap(true, Ordering::Acquire) {
+                panic!("Attempted to lock already-locked PriorityMutex");
+            }
+
+            // NOTE(unsafe): we are in a critical section with respect to all users
+            // of this mutex, and `locked` guarantees that no other reference exists.
+            let r = f(unsafe { &mut *self.data.get() });
+
+            self.locked.store(false, Ordering::Release);
+
+            r
+        })
+    }
+
+    #[cfg(has_basepri)]
+    fn critical_section<R>(&self, f: impl FnOnce() -> R) -> R {
+        use crate::backend::{Active, Backend};
+
+        let ceiling = self
+            .ceiling()
+            .map(|ceiling| crate::logical2hw(ceiling, self.nvic_prio_bits))
+            .map(|hw| hw.expect("Unsupported PriorityMutex ceiling"));
+
+        match Active::vect_active() {
+            0 => {}
+            // System exceptions, such as `SysTick`.
+            1..=15 => return crate::cs::with(f),
+            number => {
+                let current = Active::priority(crate::nvic::IrqNumber(number - 16));
+
+                // A lower hardware priority value means a higher priority level.
+                if ceiling.map(|ceiling| current < ceiling).unwrap_or(true) {
+                    panic!("Attempted to lock PriorityMutex from interrupt with a priority higher than its ceiling");
+                }
+            }
+        }
+
+        match ceiling {
+            None => f(),
+            // A `BASEPRI` of 0 does not mask any interrupts, so the highest
+            // priority level requires a global critical section.
+            Some(0) => crate::cs::with(f),
+            Some(ceiling) => {
+                let old = Active::basepri();
+                Active::set_basepri_max(ceiling);
+                let r = f();
+                // NOTE(unsafe): we restore the value that `BASEPRI` had before
+                // the lock was taken.
+                unsafe { Active::set_basepri(old) };
+                r
+            }
+        }
+    }
+
+    #[cfg(not(has_basepri))]
+    fn critical_section<R>(&self, f: impl FnOnce() -> R) -> R {
+        crate::cs::with(f)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn ceiling() {
+    let mutex = PriorityMutex::new((), 4);
+    assert_eq!(mutex.ceiling(), None);
+
+    for logical in [3, 7, 5] {
+        mutex.raise_ceiling(NonZeroU8::new(logical).unwrap());
+    }
+
+    assert_eq!(mutex.ceiling(), NonZeroU8::new(7));
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[test]
+fn lock() {
+    extern crate std;
+
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use crate::{
+        backend::{Active, Backend},
+        nvic::IrqNumber,
+        MockInterrupt, MockNvicRegistration, Priority,
+    };
+
+    struct Irq200;
+
+    impl MockInterrupt for Irq200 {
+        type Interrupt = IrqNumber;
+        const INTERRUPT: IrqNumber = IrqNumber(200);
+    }
+
+    fn panics(f: impl FnOnce()) -> bool {
+        catch_unwind(AssertUnwindSafe(f)).is_err()
+    }
+
+    // The ceiling is raised to the priority of the registration.
+    let registration = MockNvicRegistration::<Irq200>::new(Priority::new(3));
+    let mutex = PriorityMutex::new(0u32, 4);
+    mutex.add_user(&&registration);
+    assert_eq!(mutex.ceiling(), NonZeroU8::new(3));
+
+    // Locking from thread mode raises `BASEPRI` to the ceiling, and restores it afterwards.
+    mutex.lock(|data| {
+        if cfg!(has_basepri) {
+            assert_eq!(Active::basepri(), 0xD0);
+        }
+        *data += 1;
+    });
+    assert_eq!(Active::basepri(), 0);
+
+    // Locking the mutex from within `lock` panics.
+    let reentrant = PriorityMutex::new((), 4);
+    assert!(panics(|| reentrant.lock(|_| reentrant.lock(|_| {}))));
+
+    #[cfg(has_basepri)]
+    {
+        use cortex_m::peripheral::scb::{Exception, VectActive};
+
+        use crate::backend::Simulated;
+
+        // NOTE(unsafe): the simulated NVIC is not used for mask-based critical sections.
+        unsafe { Active::set_priority(IrqNumber(201), 0xB0) };
+
+        // Locking from an interrupt with a priority higher than the ceiling panics.
+        Simulated::set_vect_active(VectActive::Interrupt { irqn: 201 });
+        assert!(panics(|| mutex.lock(|_| {})));
+
+        // Locking from a system exception uses a global critical section instead of `BASEPRI`.
+        Simulated::set_vect_active(VectActive::Exception(Exception::SysTick));
+        mutex.lock(|data| {
+            assert_eq!(Active::basepri(), 0);
+            if cfg!(not(feature = "critical-section")) {
+                assert!(Simulated::in_critical_section());
+            }
+            *data += 1;
+        });
+
+        Simulated::set_vect_active(VectActive::ThreadMode);
+    }
+
+    assert_eq!(mutex.lock(|data| *data), if cfg!(has_basepri) { 2 } else { 1 });
+}
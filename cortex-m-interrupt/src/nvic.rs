@@ -1,6 +1,14 @@
-use cortex_m::{interrupt::InterruptNumber, peripheral::NVIC};
+use core::num::NonZeroU8;
 
-use crate::InterruptRegistration;
+use cortex_m::{
+    interrupt::InterruptNumber,
+    peripheral::{NVIC, SCB},
+};
+
+use crate::{
+    backend::{Active, Backend},
+    InterruptRegistration, OccupyWith, Priority,
+};
 
 /// An [`NVIC`] interrupt registration.
 ///
@@ -12,19 +20,239 @@ pub trait NvicInterruptRegistration<T: InterruptNumber>: InterruptRegistration {
     /// The interrupt number that this [`NvicInterruptRegistration`] is associated with.
     const INTERRUPT_NUMBER: T;
 
+    /// The core that this [`NvicInterruptRegistration`] is associated with, or `None`
+    /// if it is not associated with a specific core.
+    const CORE: Option<u8> = None;
+
     fn number(&self) -> T {
         Self::INTERRUPT_NUMBER
     }
+
+    /// The logical priority that this [`NvicInterruptRegistration`] configures
+    /// its interrupt with.
+    fn logical_priority(&self) -> NonZeroU8;
+
+    /// The logical sub-priority that this [`NvicInterruptRegistration`] configures
+    /// its interrupt with.
+    fn logical_sub_priority(&self) -> u8 {
+        0
+    }
+
+    /// Occupy this registration with `f`, and return an [`NvicOccupation`] that can
+    /// be used to mask the occupied interrupt.
+    ///
+    /// Calling `occupy_nvic` more than once for the same interrupt will panic.
+    fn occupy_nvic(self, f: fn()) -> NvicOccupation<T>
+    where
+        Self: Sized,
+    {
+        let interrupt = self.number();
+        let priority = self.logical_priority();
+        let sub_priority = self.logical_sub_priority();
+        self.occupy(f);
+        NvicOccupation {
+            interrupt,
+            priority,
+            sub_priority,
+        }
+    }
+
+    /// Occupy this registration with `f`, which is passed the interrupt number of this
+    /// registration every time it runs, and return an [`NvicOccupation`] that can be used to
+    /// mask the occupied interrupt.
+    ///
+    /// This allows a single function to serve several identical peripherals, and to look up the
+    /// state of each instance by its interrupt number. Calling `occupy_with_number` more than
+    /// once for the same interrupt will panic.
+    fn occupy_with_number(self, f: fn(T)) -> NvicOccupation<T>
+    where
+        Self: Sized + OccupyWith<T>,
+    {
+        let interrupt = self.number();
+        let priority = self.logical_priority();
+        let sub_priority = self.logical_sub_priority();
+        self.occupy_with(f);
+        NvicOccupation {
+            interrupt,
+            priority,
+            sub_priority,
+        }
+    }
+}
+
+/// An occupied [`NVIC`] interrupt.
+///
+/// An [`NvicOccupation`] is returned by [`NvicInterruptRegistration::occupy_nvic`].
+#[derive(Clone, Copy)]
+pub struct NvicOccupation<T: InterruptNumber> {
+    interrupt: T,
+    priority: NonZeroU8,
+    sub_priority: u8,
+}
+
+impl<T: InterruptNumber> NvicOccupation<T> {
+    /// The interrupt that this [`NvicOccupation`] is associated with.
+    pub fn number(&self) -> T {
+        self.interrupt
+    }
+
+    /// The logical priority that the interrupt of this occupation was configured with.
+    pub fn logical_priority(&self) -> NonZeroU8 {
+        self.priority
+    }
+
+    /// The logical sub-priority that the interrupt of this occupation was configured with.
+    pub fn logical_sub_priority(&self) -> u8 {
+        self.sub_priority
+    }
+
+    /// Read back the logical priority that the interrupt of this occupation currently has, for an
+    /// NVIC with `nvic_prio_bits` preemption priority bits.
+    ///
+    /// This differs from [`NvicOccupation::logical_priority`] if the priority of the interrupt was
+    /// changed after it was occupied. See [`logical_priority_of`](crate::logical_priority_of).
+    pub fn current_logical_priority(&self, nvic_prio_bits: u8) -> Option<Priority> {
+        crate::priority::logical_priority_of_number(self.interrupt.number() + 16, nvic_prio_bits)
+    }
+
+    /// Mask the interrupt of this occupation in the NVIC.
+    pub fn mask(&self) {
+        Active::mask(self.interrupt);
+    }
+
+    /// Unmask the interrupt of this occupation in the NVIC.
+    ///
+    /// # Safety
+    /// Unmasking the interrupt can break mask-based critical sections, such as
+    /// those created by [`NvicOccupation::masked`].
+    pub unsafe fn unmask(&self) {
+        Active::unmask(self.interrupt);
+    }
+
+    /// Call `f` while the interrupt of this occupation is masked in the NVIC.
+    ///
+    /// This provides a critical section with respect to this occupation only: other
+    /// interrupts can still preempt `f`. After `f` returns, the interrupt is unmasked
+    /// only if it was unmasked before.
+    pub fn masked<R>(&self, f: impl FnOnce() -> R) -> R {
+        Self::masked_all(core::slice::from_ref(self), f)
+    }
+
+    /// Call `f` while the interrupts of all of `occupations` are masked in the NVIC.
+    ///
+    /// After `f` returns, each interrupt is unmasked only if it was unmasked before.
+    pub fn masked_all<R>(occupations: &[Self], f: impl FnOnce() -> R) -> R {
+        // One bit for each of the (at most) 496 interrupts: ARMv8-M supports up to 480.
+        let mut enabled = [0u32; 16];
+
+        for occupation in occupations {
+            let number = occupation.interrupt.number() as usize;
+            if Active::is_enabled(occupation.interrupt) {
+                enabled[number / 32] |= 1 << (number % 32);
+            }
+            Active::mask(occupation.interrupt);
+        }
+
+        // Ensure that the interrupts are masked before `f` is executed.
+        Active::barrier();
+
+        let r = f();
+
+        for occupation in occupations {
+            let number = occupation.interrupt.number() as usize;
+            if enabled[number / 32] & (1 << (number % 32)) != 0 {
+                // NOTE(unsafe): the interrupt was unmasked before we masked it.
+                unsafe { Active::unmask(occupation.interrupt) };
+            }
+        }
+
+        r
+    }
+}
+
+/// An [`InterruptNumber`] obtained from the number of another [`InterruptNumber`].
+#[derive(Clone, Copy)]
+pub(crate) struct IrqNumber(pub u16);
+
+// NOTE(unsafe): the wrapped number is always obtained from another
+// implementor of `InterruptNumber`, or from the NVIC itself.
+unsafe impl InterruptNumber for IrqNumber {
+    fn number(self) -> u16 {
+        self.0
+    }
+}
+
+/// The amount of priority bits implemented by an NVIC, split into preemption priority (group priority)
+/// bits and sub-priority bits according to the priority grouping (`AIRCR.PRIGROUP`).
+///
+/// Only the preemption priority determines whether an interrupt can preempt another. The sub-priority
+/// only determines which of several pending interrupts with the same preemption priority is serviced first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrioBits {
+    /// The amount of preemption priority bits.
+    pub preempt: u8,
+    /// The amount of sub-priority bits.
+    pub sub: u8,
+}
+
+impl PrioBits {
+    /// Split `implemented` priority bits according to the priority grouping `prigroup`.
+    pub const fn new(implemented: u8, prigroup: u8) -> Self {
+        // A priority grouping of `n` places the binary point between bits `n` and `n + 1`,
+        // so bits `[7:n + 1]` hold the preemption priority.
+        let group_bits = 7 - (prigroup & 0b111);
+
+        let preempt = if implemented < group_bits {
+            implemented
+        } else {
+            group_bits
+        };
+
+        Self {
+            preempt,
+            sub: implemented - preempt,
+        }
+    }
+
+    /// The total amount of implemented priority bits.
+    pub const fn total(&self) -> u8 {
+        self.preempt + self.sub
+    }
+}
+
+/// Read the current priority grouping (`AIRCR.PRIGROUP`).
+///
+/// On ARMv6-M and ARMv8-M Baseline cores, priority grouping is not implemented and `0` is always
+/// returned.
+pub fn prigroup() -> u8 {
+    Active::prigroup()
+}
+
+/// Configure the priority grouping (`AIRCR.PRIGROUP`).
+///
+/// `_scb` is only taken as a token that proves exclusive access to the SCB; the register is
+/// written through the active backend.
+///
+/// # Safety
+/// Changing the priority grouping changes which interrupts can preempt each other, and
+/// should only be done before any interrupts are occupied.
+///
+/// Priority grouping is not implemented on ARMv6-M and ARMv8-M Baseline cores, and calling this
+/// function has no effect there.
+pub unsafe fn set_prigroup(_scb: &mut SCB, prigroup: u8) {
+    Active::set_prigroup(prigroup)
 }
 
-/// Determine the amount of priority bits available on the current MCU.
+/// Determine the amount of priority bits available on the current MCU, and how they are
+/// split into preemption priority bits and sub-priority bits.
 ///
 /// This function determines the amount of priority bits available on a Cortex-M MCU by
 /// setting the priority of an interrupt to the maximum value `0xFF`, and reading the resulting
 /// priority.
 ///
 /// The count of leading ones in the resulting value indicates the amount of
-/// priority-level bits available.
+/// priority-level bits available. These bits are split according to the current priority
+/// grouping, see [`prigroup`].
 ///
 /// After performing this calculation, the priority of the placeholder interrupt is **not** restored.
 ///
@@ -43,15 +271,15 @@ pub trait NvicInterruptRegistration<T: InterruptNumber>: InterruptRegistration {
 /// [armv7m]: https://documentation-service.arm.com/static/606dc36485368c4c2b1bf62f
 /// [armv6m]: https://documentation-service.arm.com/static/5f8ff05ef86e16515cdbf826
 pub unsafe fn determine_prio_bits<T: InterruptNumber>(
-    nvic: &mut NVIC,
+    _nvic: &mut NVIC,
     placeholder_interrupt: T,
-) -> u8 {
-    nvic.set_priority(placeholder_interrupt, 0xFF);
-    let written_prio = NVIC::get_priority(placeholder_interrupt);
+) -> PrioBits {
+    Active::set_priority(placeholder_interrupt, 0xFF);
+    let written_prio = Active::priority(placeholder_interrupt);
 
     let prio_bits = written_prio.leading_ones();
 
-    prio_bits as u8
+    PrioBits::new(prio_bits as u8, prigroup())
 }
 
 /// Convert a logical priority (where higher priority number = higher priority level) to
@@ -67,7 +295,7 @@ pub unsafe fn determine_prio_bits<T: InterruptNumber>(
 /// [`cortex_m_rtic`]: https://crates.io/crates/cortex-m-rtic
 #[inline]
 #[must_use]
-pub fn logical2hw(logical: core::num::NonZeroU8, nvic_prio_bits: u8) -> Option<u8> {
+pub fn logical2hw(logical: NonZeroU8, nvic_prio_bits: u8) -> Option<u8> {
     if logical.get() <= 1 << nvic_prio_bits {
         Some(((1u8 << nvic_prio_bits) - logical.get()) << (8 - nvic_prio_bits))
     } else {
@@ -75,6 +303,54 @@ pub fn logical2hw(logical: core::num::NonZeroU8, nvic_prio_bits: u8) -> Option<u
     }
 }
 
+/// Convert a hardware priority level (where lower priority number = higher priority level) to
+/// a logical priority (where higher priority number = higher priority level).
+///
+/// This is the inverse of [`logical2hw`]. Priority bits that are not implemented by an NVIC
+/// with `nvic_prio_bits` are ignored. `None` is returned if the resulting logical priority
+/// does not fit in a `u8`, which is only the case for `hw == 0` with 8 priority bits.
+#[inline]
+#[must_use]
+pub const fn hw2logical(hw: u8, nvic_prio_bits: u8) -> Option<NonZeroU8> {
+    if nvic_prio_bits > 8 {
+        return None;
+    }
+
+    let levels = 1u16 << nvic_prio_bits;
+    let level = (hw as u16) >> (8 - nvic_prio_bits);
+    let logical = levels - level;
+
+    if logical <= u8::MAX as u16 {
+        NonZeroU8::new(logical as u8)
+    } else {
+        None
+    }
+}
+
+/// Convert a logical preemption priority and a logical sub-priority (where, for both, a higher
+/// priority number = higher priority level) to a hardware priority level, for an NVIC whose
+/// priority bits are split according to `prio_bits`.
+///
+/// The logical preemption priority `preempt` is in the range `1..=(1 << prio_bits.preempt)`,
+/// and the logical sub-priority `sub` is in the range `0..(1 << prio_bits.sub)`. `None` is
+/// returned if either is out of range.
+#[inline]
+#[must_use]
+pub fn logical2hw_grouped(preempt: NonZeroU8, sub: u8, prio_bits: PrioBits) -> Option<u8> {
+    let total = prio_bits.total();
+    let preempt_levels = 1u16 << prio_bits.preempt;
+    let sub_levels = 1u16 << prio_bits.sub;
+
+    if total > 8 || preempt.get() as u16 > preempt_levels || sub as u16 >= sub_levels {
+        return None;
+    }
+
+    let group = preempt_levels - preempt.get() as u16;
+    let sub = sub_levels - 1 - sub as u16;
+
+    Some((((group << prio_bits.sub) | sub) << (8 - total)) as u8)
+}
+
 #[cfg(test)]
 #[test]
 fn test() {
@@ -93,3 +369,69 @@ fn test() {
         }
     }
 }
+
+#[cfg(test)]
+#[test]
+fn grouped() {
+    let nz = |v| NonZeroU8::new(v).unwrap();
+
+    // 4 implemented bits, split into 2 preemption priority bits and 2 sub-priority bits.
+    let prio_bits = PrioBits::new(4, 5);
+    assert_eq!(prio_bits, PrioBits { preempt: 2, sub: 2 });
+
+    assert_eq!(logical2hw_grouped(nz(4), 3, prio_bits), Some(0x00));
+    assert_eq!(logical2hw_grouped(nz(4), 0, prio_bits), Some(0x30));
+    assert_eq!(logical2hw_grouped(nz(1), 0, prio_bits), Some(0xF0));
+    assert_eq!(logical2hw_grouped(nz(5), 0, prio_bits), None);
+    assert_eq!(logical2hw_grouped(nz(1), 4, prio_bits), None);
+
+    // Without sub-priority bits, the result is the same as that of `logical2hw`.
+    let prio_bits = PrioBits::new(4, 0);
+    assert_eq!(prio_bits, PrioBits { preempt: 4, sub: 0 });
+
+    for i in 1..=16 {
+        assert_eq!(
+            logical2hw_grouped(nz(i), 0, prio_bits),
+            logical2hw(nz(i), 4)
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn inverse() {
+    for bits in 1..=7 {
+        for i in 1..=(1u8 << bits) {
+            let logical = NonZeroU8::new(i).unwrap();
+            let hw = logical2hw(logical, bits).unwrap();
+            assert_eq!(hw2logical(hw, bits), Some(logical));
+        }
+    }
+
+    // Non-implemented bits are ignored.
+    assert_eq!(hw2logical(0xFF, 4), NonZeroU8::new(1));
+    assert_eq!(hw2logical(0x0F, 4), NonZeroU8::new(16));
+}
+
+#[cfg(test)]
+#[test]
+fn masked_all() {
+    let nz = |v| NonZeroU8::new(v).unwrap();
+    let occupation = |number| NvicOccupation {
+        interrupt: IrqNumber(number),
+        priority: nz(1),
+        sub_priority: 0,
+    };
+
+    // Interrupts above 255 only exist on ARMv8-M.
+    let occupations = [occupation(300), occupation(479)];
+    unsafe { Active::unmask(IrqNumber(300)) };
+
+    NvicOccupation::masked_all(&occupations, || {
+        assert!(!Active::is_enabled(IrqNumber(300)));
+        assert!(!Active::is_enabled(IrqNumber(479)));
+    });
+
+    assert!(Active::is_enabled(IrqNumber(300)));
+    assert!(!Active::is_enabled(IrqNumber(479)));
+}
@@ -0,0 +1,334 @@
+/// Mark a point at which an interrupt may preempt the currently running code.
+///
+/// Outside of [`Preemption::explore`], or without the `mock` feature, this does nothing. During
+/// exploration, the interrupts of the [`Preemption`] that may preempt the currently running code
+/// are injected here.
+///
+/// [`Preemption`]: crate::Preemption
+/// [`Preemption::explore`]: crate::Preemption::explore
+#[inline(always)]
+pub fn preemption_point() {
+    #[cfg(all(feature = "mock", not(target_arch = "arm")))]
+    simulator::preemption_point();
+}
+
+#[cfg(all(feature = "mock", not(target_arch = "arm")))]
+pub use simulator::{Failure, Injection, Preemption};
+
+#[cfg(all(feature = "mock", not(target_arch = "arm")))]
+mod simulator {
+    extern crate std;
+
+    use std::{
+        boxed::Box,
+        cell::RefCell,
+        fmt,
+        panic::{self, AssertUnwindSafe},
+        string::String,
+        vec::Vec,
+    };
+
+    use core::num::NonZeroU8;
+
+    use cortex_m::interrupt::InterruptNumber;
+
+    use crate::{
+        backend::{Backend, Simulated},
+        nvic::IrqNumber,
+        Priority,
+    };
+
+    #[derive(Clone, Copy)]
+    struct Interrupt {
+        name: &'static str,
+        number: u16,
+        priority: u8,
+        handler: fn(),
+    }
+
+    impl Interrupt {
+        /// Whether this interrupt is masked, either in the [`Simulated`] NVIC, or by the
+        /// [`Simulated`] `BASEPRI`.
+        fn is_masked(&self) -> bool {
+            if !Simulated::is_enabled(IrqNumber(self.number)) {
+                return true;
+            }
+
+            let basepri = Simulated::basepri();
+            let hw = NonZeroU8::new(self.priority)
+                .and_then(|logical| crate::logical2hw(logical, Simulated::implemented_bits()));
+
+            // A `BASEPRI` of 0 does not mask any interrupts, and a lower hardware priority value
+            // means a higher priority level.
+            basepri != 0 && hw.map(|hw| hw >= basepri).unwrap_or(true)
+        }
+    }
+
+    /// A deterministic simulator of interrupts preempting thread-mode code and each other.
+    ///
+    /// [`Preemption::explore`] runs a closure, which represents thread-mode code, once for every
+    /// possible interleaving of the interrupts added with [`Preemption::interrupt`]. In each
+    /// interleaving, every interrupt is injected at most once, at one of the calls to
+    /// [`preemption_point`]. An interrupt is only injected if its priority is higher than that of
+    /// the code that is currently running, which is the lowest priority for thread-mode code, if
+    /// no critical section of the [`Simulated`] backend is active, and if it is not masked: it must
+    /// be enabled in the [`Simulated`] NVIC, and its priority must be higher than the [`Simulated`]
+    /// `BASEPRI` (if that is set). The hardware priority that is compared with `BASEPRI` is derived
+    /// from the logical priority of the interrupt using the amount of priority bits that the
+    /// [`Simulated`] NVIC implements, without priority grouping.
+    ///
+    /// An interleaving fails if it panics, for instance because an assertion at the end of the
+    /// thread-mode closure does not hold. The thread-mode closure must therefore reset all state
+    /// that the previous interleaving may have changed.
+    ///
+    /// With the `critical-section` feature, critical sections do not go through the [`Simulated`]
+    /// backend, so they do not prevent interrupts from being injected.
+    ///
+    /// ```rust,ignore
+    /// static EXTI1: MockNvicRegistration<Exti1> = MockNvicRegistration::new(Priority::new(2));
+    ///
+    /// Preemption::new()
+    ///     .interrupt("EXTI1", pac::Interrupt::EXTI1, Priority::new(2), || { EXTI1.fire(); })
+    ///     .explore(|| {
+    ///         let pin = AsyncExtiPin::new(pin, &EXTI1);
+    ///         // ...
+    ///     })
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`preemption_point`]: crate::preemption_point
+    pub struct Preemption {
+        interrupts: Vec<Interrupt>,
+        max_interleavings: usize,
+    }
+
+    /// The injection of an interrupt at a preemption point.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Injection {
+        /// The name of the injected interrupt.
+        pub interrupt: &'static str,
+        /// The index of the preemption point (counted from the start of the interleaving) at which
+        /// the interrupt was injected.
+        pub point: usize,
+    }
+
+    /// An interleaving that failed.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Failure {
+        /// The interrupts that were injected in the failing interleaving, in the order they were
+        /// injected.
+        pub interleaving: Vec<Injection>,
+        /// The panic message of the failing interleaving.
+        pub message: String,
+    }
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "interleaving failed: {}", self.message)?;
+
+            if self.interleaving.is_empty() {
+                write!(f, " (no interrupts were injected)")
+            } else {
+                write!(f, " (injected")?;
+                for (index, injection) in self.interleaving.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { "," };
+                    write!(
+                        f,
+                        "{} `{}` at preemption point {}",
+                        separator, injection.interrupt, injection.point
+                    )?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+
+    /// The state of a single interleaving.
+    struct Run {
+        interrupts: Vec<Interrupt>,
+        fired: Vec<bool>,
+        /// The priorities of the interrupts that are currently running.
+        running: Vec<u8>,
+        /// The choice made at every decision, and the amount of options it had.
+        choices: Vec<(usize, usize)>,
+        decision: usize,
+        point: usize,
+        injections: Vec<Injection>,
+    }
+
+    std::thread_local! {
+        static RUN: RefCell<Option<Run>> = const { RefCell::new(None) };
+    }
+
+    impl Preemption {
+        /// Create a simulator without any interrupts.
+        pub fn new() -> Self {
+            Self {
+                interrupts: Vec::new(),
+                max_interleavings: 100_000,
+            }
+        }
+
+        /// Add the interrupt `interrupt` named `name` with logical priority `priority`, that runs
+        /// `handler` when it is injected.
+        ///
+        /// `interrupt` is only injected while it is enabled in the [`Simulated`] NVIC, so it must
+        /// be unmasked (for instance by occupying it) before it can be injected.
+        pub fn interrupt<I: InterruptNumber>(
+            mut self,
+            name: &'static str,
+            interrupt: I,
+            priority: Priority,
+            handler: fn(),
+        ) -> Self {
+            self.interrupts.push(Interrupt {
+                name,
+                number: interrupt.number(),
+                priority: priority.get(),
+                handler,
+            });
+            self
+        }
+
+        /// Stop exploring after `max_interleavings` interleavings (default 100000).
+        pub fn max_interleavings(mut self, max_interleavings: usize) -> Self {
+            self.max_interleavings = max_interleavings;
+            self
+        }
+
+        /// Run `thread` once for every interleaving of the interrupts, until an interleaving fails.
+        ///
+        /// Returns the amount of interleavings that were explored, or the first [`Failure`].
+        pub fn explore(&self, mut thread: impl FnMut()) -> Result<usize, Failure> {
+            let mut choices = Vec::new();
+            let mut interleavings = 0;
+
+            while interleavings < self.max_interleavings {
+                let run = Run {
+                    interrupts: self.interrupts.clone(),
+                    fired: self.interrupts.iter().map(|_| false).collect(),
+                    running: Vec::new(),
+                    choices,
+                    decision: 0,
+                    point: 0,
+                    injections: Vec::new(),
+                };
+
+                RUN.with(|current| {
+                    if current.borrow_mut().replace(run).is_some() {
+                        panic!("`Preemption::explore` can not be nested.");
+                    }
+                });
+
+                let result = panic::catch_unwind(AssertUnwindSafe(&mut thread));
+                let run = RUN.with(|current| current.borrow_mut().take()).unwrap();
+                interleavings += 1;
+
+                if let Err(payload) = result {
+                    return Err(Failure {
+                        interleaving: run.injections,
+                        message: message(payload),
+                    });
+                }
+
+                // Move on to the next interleaving by advancing the last decision that has
+                // options left, and forgetting about the decisions after it.
+                choices = run.choices;
+                loop {
+                    match choices.pop() {
+                        Some((choice, options)) if choice + 1 < options => {
+                            choices.push((choice + 1, options));
+                            break;
+                        }
+                        Some(_) => {}
+                        None => return Ok(interleavings),
+                    }
+                }
+            }
+
+            Ok(interleavings)
+        }
+    }
+
+    impl Default for Preemption {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    fn message(payload: Box<dyn core::any::Any + Send>) -> String {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            String::from(*message)
+        } else if let Ok(message) = payload.downcast::<String>() {
+            *message
+        } else {
+            String::from("<unknown panic payload>")
+        }
+    }
+
+    /// Decide which interrupt, if any, to inject at preemption point `point`.
+    fn decide(run: &mut Run, point: usize) -> Option<fn()> {
+        if Simulated::in_critical_section() {
+            return None;
+        }
+
+        let current = run.running.last().copied().unwrap_or(0);
+        let eligible: Vec<usize> = (0..run.interrupts.len())
+            .filter(|&index| {
+                let interrupt = &run.interrupts[index];
+                !run.fired[index] && interrupt.priority > current && !interrupt.is_masked()
+            })
+            .collect();
+
+        if eligible.is_empty() {
+            return None;
+        }
+
+        let choice = match run.choices.get(run.decision) {
+            Some(&(choice, _)) => choice,
+            None => {
+                run.choices.push((0, eligible.len() + 1));
+                0
+            }
+        };
+        run.decision += 1;
+
+        // Choice `0` injects nothing.
+        let index = *eligible.get(choice.checked_sub(1)?)?;
+        let interrupt = run.interrupts[index];
+
+        run.fired[index] = true;
+        run.running.push(interrupt.priority);
+        run.injections.push(Injection {
+            interrupt: interrupt.name,
+            point,
+        });
+
+        Some(interrupt.handler)
+    }
+
+    pub(super) fn preemption_point() {
+        let point = RUN.with(|current| {
+            current.borrow_mut().as_mut().map(|run| {
+                run.point += 1;
+                run.point - 1
+            })
+        });
+
+        let point = match point {
+            Some(point) => point,
+            None => return,
+        };
+
+        while let Some(handler) = RUN.with(|current| decide(current.borrow_mut().as_mut()?, point))
+        {
+            handler();
+
+            RUN.with(|current| {
+                if let Some(run) = current.borrow_mut().as_mut() {
+                    run.running.pop();
+                }
+            });
+        }
+    }
+}
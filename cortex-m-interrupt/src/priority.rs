@@ -0,0 +1,195 @@
+use core::num::NonZeroU8;
+
+use cortex_m::peripheral::scb::VectActive;
+
+use crate::{
+    backend::{Active, Backend},
+    nvic::IrqNumber,
+};
+
+/// A logical priority, where a higher priority number means a higher priority level.
+///
+/// A [`Priority`] is never zero, which is verified when it is created. Creating one in a
+/// `const` context moves that verification to compile time:
+///
+/// ```rust
+/// use cortex_m_interrupt::Priority;
+///
+/// const PRIO_SENSORS: Priority = Priority::new(3);
+/// const PRIO_RADIO: Priority = Priority::new(5).validate(4);
+///
+/// assert!(PRIO_RADIO > PRIO_SENSORS);
+/// ```
+///
+/// A [`Priority`] (or any other constant expression that evaluates to a `u8` or a [`Priority`])
+/// can be passed to [`take_nvic_interrupt`] as the priority of an interrupt.
+///
+/// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(NonZeroU8);
+
+impl Priority {
+    /// Create a new [`Priority`] from the logical priority `logical`.
+    ///
+    /// # Panics
+    /// This function panics if `logical` is 0.
+    pub const fn new(logical: u8) -> Self {
+        match NonZeroU8::new(logical) {
+            Some(logical) => Self(logical),
+            None => panic!("Priority must be 1 or greater."),
+        }
+    }
+
+    /// Create a new [`Priority`] from the logical priority `logical`, or `None` if `logical` is 0.
+    pub const fn checked(logical: u8) -> Option<Self> {
+        match NonZeroU8::new(logical) {
+            Some(logical) => Some(Self(logical)),
+            None => None,
+        }
+    }
+
+    /// Create a new [`Priority`] from the hardware priority level `hw` of an NVIC with
+    /// `nvic_prio_bits` priority bits.
+    ///
+    /// See [`hw2logical`](crate::hw2logical).
+    pub const fn from_hw(hw: u8, nvic_prio_bits: u8) -> Option<Self> {
+        match crate::hw2logical(hw, nvic_prio_bits) {
+            Some(logical) => Some(Self(logical)),
+            None => None,
+        }
+    }
+
+    /// The logical priority number of this [`Priority`].
+    pub const fn get(self) -> u8 {
+        self.0.get()
+    }
+
+    /// The logical priority number of this [`Priority`], as a [`NonZeroU8`].
+    pub const fn logical(self) -> NonZeroU8 {
+        self.0
+    }
+
+    /// Whether this [`Priority`] is supported by an NVIC with `nvic_prio_bits` priority bits.
+    pub const fn is_supported(self, nvic_prio_bits: u8) -> bool {
+        nvic_prio_bits <= 8 && self.0.get() as u16 <= 1u16 << nvic_prio_bits
+    }
+
+    /// Return this [`Priority`] if it is supported by an NVIC with `nvic_prio_bits` priority bits.
+    ///
+    /// # Panics
+    /// This function panics if this [`Priority`] is not supported, see [`Priority::is_supported`].
+    pub const fn validate(self, nvic_prio_bits: u8) -> Self {
+        if !self.is_supported(nvic_prio_bits) {
+            panic!("Priority is not supported by the amount of NVIC priority bits.");
+        }
+        self
+    }
+
+    /// The hardware priority level of this [`Priority`] for an NVIC with `nvic_prio_bits`
+    /// priority bits, or `None` if it is not supported.
+    ///
+    /// See [`logical2hw`](crate::logical2hw).
+    pub fn to_hw(self, nvic_prio_bits: u8) -> Option<u8> {
+        crate::logical2hw(self.0, nvic_prio_bits)
+    }
+}
+
+impl From<Priority> for NonZeroU8 {
+    fn from(priority: Priority) -> Self {
+        priority.0
+    }
+}
+
+impl From<NonZeroU8> for Priority {
+    fn from(logical: NonZeroU8) -> Self {
+        Self(logical)
+    }
+}
+
+/// Read back the logical priority that `vector` currently has, for an NVIC with
+/// `nvic_prio_bits` preemption priority bits.
+///
+/// `vector` may be any interrupt or system exception. `None` is returned for thread mode and for
+/// the exceptions with a fixed priority (`NonMaskableInt` and `HardFault`), as they have no logical
+/// priority, or if the priority does not fit in a [`Priority`] (see [`hw2logical`]).
+///
+/// If the priority grouping splits the available priority bits, `nvic_prio_bits` should be the amount
+/// of preemption priority bits (see [`PrioBits`]). In that case, the returned logical priority is the
+/// preemption priority, and the sub-priority is ignored.
+///
+/// ```rust,ignore
+/// use cortex_m::peripheral::scb::{Exception, VectActive};
+///
+/// let systick = logical_priority_of(VectActive::Exception(Exception::SysTick), pac::NVIC_PRIO_BITS);
+/// let exti = logical_priority_of(VectActive::Interrupt { irqn: pac::Interrupt::EXTI1 as u8 }, pac::NVIC_PRIO_BITS);
+/// ```
+///
+/// [`hw2logical`]: crate::hw2logical
+/// [`PrioBits`]: crate::PrioBits
+pub fn logical_priority_of(vector: VectActive, nvic_prio_bits: u8) -> Option<Priority> {
+    let number = match vector {
+        VectActive::ThreadMode => 0,
+        VectActive::Interrupt { irqn } => irqn as u16 + 16,
+        VectActive::Exception(exception) => (16 + exception.irqn() as i16) as u16,
+    };
+
+    logical_priority_of_number(number, nvic_prio_bits)
+}
+
+/// The logical priority of the vector with exception number `number`.
+pub(crate) fn logical_priority_of_number(number: u16, nvic_prio_bits: u8) -> Option<Priority> {
+    let hw = match number {
+        // Thread mode, and the exceptions with a fixed priority: the exception number of
+        // `NonMaskableInt` is 2 and that of `HardFault` is 3. They do not have a System Handler
+        // Priority Register.
+        0..=3 => return None,
+        4..=15 => Active::system_handler_priority(number as u8),
+        number => Active::priority(IrqNumber(number - 16)),
+    };
+
+    Priority::from_hw(hw, nvic_prio_bits)
+}
+
+/// Read back the logical priority of the interrupt or exception that is currently executing,
+/// for an NVIC with `nvic_prio_bits` preemption priority bits.
+///
+/// See [`logical_priority_of`].
+pub fn current_logical_priority(nvic_prio_bits: u8) -> Option<Priority> {
+    logical_priority_of_number(Active::vect_active(), nvic_prio_bits)
+}
+
+/// The priority argument of [`take_nvic_interrupt`], which converts both `u8` and
+/// [`Priority`] expressions to a [`Priority`] in a `const` context.
+///
+/// [`take_nvic_interrupt`]: crate::take_nvic_interrupt
+#[doc(hidden)]
+pub struct PriorityValue<T>(pub T);
+
+impl PriorityValue<u8> {
+    pub const fn priority(self) -> Priority {
+        Priority::new(self.0)
+    }
+}
+
+impl PriorityValue<Priority> {
+    pub const fn priority(self) -> Priority {
+        self.0
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn priority() {
+    assert_eq!(Priority::checked(0), None);
+    assert_eq!(PriorityValue(3u8).priority(), Priority::new(3));
+    assert_eq!(PriorityValue(Priority::new(3)).priority(), Priority::new(3));
+
+    assert!(Priority::new(16).is_supported(4));
+    assert!(!Priority::new(17).is_supported(4));
+
+    let priority = Priority::new(5);
+    assert_eq!(
+        priority.to_hw(4).and_then(|hw| Priority::from_hw(hw, 4)),
+        Some(priority)
+    );
+}
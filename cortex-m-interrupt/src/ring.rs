@@ -0,0 +1,424 @@
+use core::{future::poll_fn, mem::MaybeUninit, ptr, task::Poll};
+
+use crate::{
+    sync::{AtomicBool, AtomicUsize, Ordering, UnsafeCell},
+    AtomicWaker,
+};
+
+/// What a [`RingProducer`] does with a value that is enqueued while the [`RingBuffer`] is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// The new value is dropped.
+    DropNew,
+    /// The oldest value in the buffer is dropped to make room for the new value.
+    OverwriteOldest,
+    /// The new value is dropped, and counted as an overrun. The amount of overruns can be read
+    /// back using [`RingConsumer::take_overruns`].
+    CountOverruns,
+}
+
+/// A bounded, lock-free single-producer single-consumer ring buffer with room for `N` values,
+/// for passing data such as received bytes or samples from an interrupt to thread mode.
+///
+/// The buffer has a single [`RingProducer`], which is used by an occupation, and a single
+/// [`RingConsumer`], which stays in thread mode. Enqueueing and dequeueing values does not use
+/// critical sections. Waking the consumer in [`RingProducer::enqueue`] and registering its waker
+/// in [`RingConsumer::recv`] do, as they go through an [`AtomicWaker`].
+///
+/// What happens to a value that is enqueued while the buffer is full is determined by the
+/// [`Overflow`] policy of the buffer. With [`Overflow::OverwriteOldest`], the producer would
+/// overwrite the slot that the consumer reads the oldest value from, so enqueueing and dequeueing
+/// a value do use a short critical section with that policy. If the producer and the consumer run
+/// on different cores, the `critical-section` feature must then be enabled, with a critical
+/// section implementation that is safe on multi-core systems.
+///
+/// Values that are overwritten, or that are still in the buffer when it is dropped, are not
+/// dropped themselves, which is why the values in the buffer must be [`Copy`].
+///
+/// Every value that is enqueued wakes the task that is waiting in [`RingConsumer::recv`], if any.
+///
+/// # Usage
+///
+/// As an occupation is a `fn()`, it can not take ownership of the producer. Instead, the producer
+/// is moved into a `static` that the occupation uses, for example behind a [`Mutex`]:
+///
+/// ```rust,ignore
+/// use core::cell::RefCell;
+/// use cortex_m::interrupt::Mutex;
+/// use cortex_m_interrupt::{InterruptRegistration, Overflow, RingBuffer, RingConsumer, RingProducer};
+///
+/// static RX: RingBuffer<u8, 64> = RingBuffer::new(Overflow::CountOverruns);
+/// static RX_PRODUCER: Mutex<RefCell<Option<RingProducer<'static, u8, 64>>>> =
+///     Mutex::new(RefCell::new(None));
+///
+/// fn start(registration: impl InterruptRegistration) -> RingConsumer<'static, u8, 64> {
+///     let (consumer, producer) = RX.try_split().unwrap();
+///     cortex_m::interrupt::free(|cs| RX_PRODUCER.borrow(cs).replace(Some(producer)));
+///
+///     registration.occupy(|| {
+///         cortex_m::interrupt::free(|cs| {
+///             if let Some(producer) = RX_PRODUCER.borrow(cs).borrow_mut().as_mut() {
+///                 let _ = producer.enqueue(read_byte());
+///             }
+///         })
+///     });
+///
+///     consumer
+/// }
+///
+/// async fn task(mut consumer: RingConsumer<'static, u8, 64>) {
+///     loop {
+///         let byte = consumer.recv().await;
+///         // ...
+///     }
+/// }
+/// ```
+///
+/// [`Mutex`]: cortex_m::interrupt::Mutex
+pub struct RingBuffer<T, const N: usize> {
+    buffer: [UnsafeCell<MaybeUninit<T>>; N],
+    /// The amount of values that were ever enqueued, wrapping around at `usize::MAX`. Only written
+    /// by the producer.
+    head: AtomicUsize,
+    /// The amount of values that were ever dequeued or overwritten, wrapping around at
+    /// `usize::MAX`. Written by the consumer, and by the producer with [`Overflow::OverwriteOldest`].
+    ///
+    /// The indices are only reduced modulo `N` to access a slot, so that the consumer does not
+    /// mistake a `tail` that the producer moved around the whole buffer for the one it read a
+    /// value at, unless the producer wraps it around all of `usize` in the meantime.
+    tail: AtomicUsize,
+    overflow: Overflow,
+    overruns: AtomicUsize,
+    split: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    /// Create a new, empty ring buffer that handles overflows according to `overflow`.
+    ///
+    /// # Panics
+    /// If `N` is `0`.
+    #[cfg(not(loom))]
+    pub const fn new(overflow: Overflow) -> Self {
+        assert!(N > 0, "A RingBuffer must have room for at least one value.");
+
+        Self {
+            buffer: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overflow,
+            overruns: AtomicUsize::new(0),
+            split: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Create a new, empty ring buffer that handles overflows according to `overflow`.
+    ///
+    /// # Panics
+    /// If `N` is `0`.
+    #[cfg(loom)]
+    pub fn new(overflow: Overflow) -> Self {
+        assert!(N > 0, "A RingBuffer must have room for at least one value.");
+
+        Self {
+            buffer: core::array::from_fn(|_| UnsafeCell::new(MaybeUninit::uninit())),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overflow,
+            overruns: AtomicUsize::new(0),
+            split: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Split this ring buffer into a [`RingConsumer`] and a [`RingProducer`].
+    pub fn split(&mut self) -> (RingConsumer<'_, T, N>, RingProducer<'_, T, N>) {
+        (RingConsumer { ring: self }, RingProducer { ring: self })
+    }
+
+    /// Split this ring buffer into a [`RingConsumer`] and a [`RingProducer`], if it has not been
+    /// split using `try_split` before.
+    ///
+    /// Unlike [`RingBuffer::split`], this does not require a mutable reference, so that a
+    /// `static` ring buffer can be split without `unsafe`.
+    pub fn try_split(&self) -> Option<(RingConsumer<'_, T, N>, RingProducer<'_, T, N>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            Some((RingConsumer { ring: self }, RingProducer { ring: self }))
+        }
+    }
+
+    /// The amount of values that fit in this ring buffer.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// The amount of values that are currently in this ring buffer.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        // The producer may enqueue (and overwrite) values between the two loads, so the distance
+        // can exceed the capacity.
+        Self::distance(tail, head).min(N)
+    }
+
+    /// Whether this ring buffer is currently empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The amount of values from `tail` up to `head`.
+    fn distance(tail: usize, head: usize) -> usize {
+        head.wrapping_sub(tail)
+    }
+
+    /// Run `f` in a critical section if the producer may overwrite values, so that it can not
+    /// overwrite a slot while the consumer reads it.
+    fn exclusive<R>(&self, f: impl FnOnce() -> R) -> R {
+        match self.overflow {
+            Overflow::OverwriteOldest => crate::cs::with(f),
+            Overflow::DropNew | Overflow::CountOverruns => f(),
+        }
+    }
+}
+
+// NOTE(unsafe): the values in the buffer are only accessed through the `RingConsumer` and
+// `RingProducer`, which are moved to the execution contexts that use them.
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+/// Write handle to a [`RingBuffer`].
+pub struct RingProducer<'a, T, const N: usize> {
+    ring: &'a RingBuffer<T, N>,
+}
+
+impl<'a, T: Copy, const N: usize> RingProducer<'a, T, N> {
+    /// Enqueue `value`, and wake the task that is waiting in [`RingConsumer::recv`], if any.
+    ///
+    /// If the ring buffer is full, `value` is handled according to its [`Overflow`] policy. If
+    /// `value` is dropped because of that, it is returned as an error.
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let ring = self.ring;
+
+        ring.exclusive(|| Self::push(ring, value))?;
+        ring.waker.wake();
+
+        Ok(())
+    }
+
+    fn push(ring: &RingBuffer<T, N>, value: T) -> Result<(), T> {
+        let head = ring.head.load(Ordering::Relaxed);
+
+        loop {
+            let tail = ring.tail.load(Ordering::Acquire);
+            if RingBuffer::<T, N>::distance(tail, head) < N {
+                break;
+            }
+
+            match ring.overflow {
+                Overflow::DropNew => return Err(value),
+                Overflow::CountOverruns => {
+                    ring.overruns.fetch_add(1, Ordering::Relaxed);
+                    return Err(value);
+                }
+                // If this fails, the consumer dequeued the oldest value in the meantime.
+                Overflow::OverwriteOldest => {
+                    if ring
+                        .tail
+                        .compare_exchange(
+                            tail,
+                            tail.wrapping_add(1),
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        )
+                        .is_ok()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+
+        crate::preemption_point();
+        // NOTE(unsafe): the consumer released this slot, or, with `Overflow::OverwriteOldest`,
+        // can not be reading it, as we are in a critical section.
+        ring.buffer[head % N].with_mut(|slot| unsafe { ptr::write(slot, MaybeUninit::new(value)) });
+        crate::preemption_point();
+        ring.head.store(head.wrapping_add(1), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Whether the ring buffer is currently full.
+    pub fn is_full(&self) -> bool {
+        self.ring.len() == N
+    }
+}
+
+// NOTE(unsafe): we guarantee the safety by only writing a slot after the consumer released it,
+// or while the consumer can not be reading it.
+unsafe impl<'a, T: Send, const N: usize> Send for RingProducer<'a, T, N> {}
+
+/// Read handle to a [`RingBuffer`].
+pub struct RingConsumer<'a, T, const N: usize> {
+    ring: &'a RingBuffer<T, N>,
+}
+
+impl<'a, T: Copy, const N: usize> RingConsumer<'a, T, N> {
+    /// Try dequeueing the oldest value in the ring buffer.
+    pub fn dequeue(&mut self) -> Option<T> {
+        let ring = self.ring;
+
+        ring.exclusive(|| Self::pop(ring))
+    }
+
+    fn pop(ring: &RingBuffer<T, N>) -> Option<T> {
+        loop {
+            let tail = ring.tail.load(Ordering::Acquire);
+            let head = ring.head.load(Ordering::Acquire);
+            if tail == head {
+                return None;
+            }
+
+            crate::preemption_point();
+            // NOTE(unsafe): the producer only writes this slot again after we released it, or,
+            // with `Overflow::OverwriteOldest`, can not write it while we are in a critical
+            // section.
+            let value = ring.buffer[tail % N].with(|slot| unsafe { ptr::read(slot) });
+            crate::preemption_point();
+
+            if ring
+                .tail
+                .compare_exchange(
+                    tail,
+                    tail.wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok()
+            {
+                // NOTE(unsafe): the slot was written before `head` was moved past it.
+                return Some(unsafe { value.assume_init() });
+            }
+        }
+    }
+
+    /// Wait for a value to be enqueued, and dequeue it.
+    pub async fn recv(&mut self) -> T {
+        poll_fn(|cx| {
+            if let Some(value) = self.dequeue() {
+                return Poll::Ready(value);
+            }
+
+            // Register before checking again, so that a value that is enqueued in between is
+            // not missed.
+            self.ring.waker.register(cx.waker());
+
+            match self.dequeue() {
+                Some(value) => Poll::Ready(value),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// The amount of values that were dropped with [`Overflow::CountOverruns`] since the last call
+    /// to `take_overruns`.
+    pub fn take_overruns(&mut self) -> usize {
+        self.ring.overruns.swap(0, Ordering::Relaxed)
+    }
+
+    /// Whether the ring buffer is currently empty.
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+}
+
+// NOTE(unsafe): we guarantee the safety by only reading a slot after the producer published it.
+unsafe impl<'a, T: Send, const N: usize> Send for RingConsumer<'a, T, N> {}
+
+#[cfg(all(test, not(loom)))]
+#[test]
+fn overflow() {
+    let mut ring = RingBuffer::<u8, 3>::new(Overflow::DropNew);
+    let (mut consumer, mut producer) = ring.split();
+
+    // Wrap around the indices a few times.
+    for round in 0..4 {
+        for value in 0..3 {
+            assert_eq!(producer.enqueue(round * 3 + value), Ok(()));
+        }
+        assert!(producer.is_full());
+        assert_eq!(producer.enqueue(42), Err(42));
+
+        for value in 0..3 {
+            assert_eq!(consumer.dequeue(), Some(round * 3 + value));
+        }
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    let mut ring = RingBuffer::<u8, 3>::new(Overflow::OverwriteOldest);
+    let (mut consumer, mut producer) = ring.split();
+
+    for value in 0..5 {
+        assert_eq!(producer.enqueue(value), Ok(()));
+    }
+    assert_eq!(consumer.dequeue(), Some(2));
+    assert_eq!(producer.enqueue(5), Ok(()));
+    assert_eq!(consumer.dequeue(), Some(3));
+    assert_eq!(consumer.dequeue(), Some(4));
+    assert_eq!(consumer.dequeue(), Some(5));
+    assert_eq!(consumer.dequeue(), None);
+
+    let mut ring = RingBuffer::<u8, 1>::new(Overflow::CountOverruns);
+    let (mut consumer, mut producer) = ring.split();
+
+    assert_eq!(producer.enqueue(1), Ok(()));
+    assert_eq!(producer.enqueue(2), Err(2));
+    assert_eq!(producer.enqueue(3), Err(3));
+    assert_eq!(consumer.take_overruns(), 2);
+    assert_eq!(consumer.take_overruns(), 0);
+    assert_eq!(consumer.dequeue(), Some(1));
+}
+
+#[cfg(all(test, not(loom)))]
+#[test]
+fn recv() {
+    use core::{future::Future, pin::pin, task::Context};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Wake, Waker},
+    };
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut ring = RingBuffer::<u8, 4>::new(Overflow::DropNew);
+    let (mut consumer, mut producer) = ring.split();
+
+    {
+        let mut recv = pin!(consumer.recv());
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+
+        producer.enqueue(7).unwrap();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(7));
+    }
+
+    // A value that is available already is returned without waiting.
+    producer.enqueue(8).unwrap();
+    assert_eq!(pin!(consumer.recv()).poll(&mut cx), Poll::Ready(8));
+}
@@ -0,0 +1,201 @@
+use core::cell::UnsafeCell;
+
+use cortex_m::interrupt::InterruptNumber;
+
+use crate::{ChainedRegistration, HandlerList, NvicInterruptRegistration};
+
+/// The storage of an interrupt that is shared by `N` sources, such as `EXTI9_5` on STM32, which
+/// serves EXTI lines 5 up to and including 9.
+///
+/// A shared interrupt is a [`HandlerList`] in which every position is paired with a predicate that
+/// returns whether its source fired. It is split into `N` [`SharedRegistration`]s, one for each
+/// source, using [`split_interrupt`]. The occupation of the shared interrupt calls the predicates
+/// of all occupied sources in order, and runs the occupation of every source whose predicate
+/// returns `true`.
+///
+/// The predicates should only read the state of their source. Clearing the source is left to
+/// its occupation.
+///
+/// [`split_interrupt`]: crate::split_interrupt
+pub struct SharedInterrupt<const N: usize> {
+    list: HandlerList<N>,
+    fired: UnsafeCell<[fn() -> bool; N]>,
+}
+
+impl<const N: usize> SharedInterrupt<N> {
+    /// Create the storage of a shared interrupt that has not been split yet.
+    #[cfg(not(loom))]
+    pub const fn new() -> Self {
+        Self {
+            list: HandlerList::new(),
+            fired: UnsafeCell::new([never; N]),
+        }
+    }
+
+    /// Create the storage of a shared interrupt that has not been split yet.
+    #[cfg(loom)]
+    pub fn new() -> Self {
+        Self {
+            list: HandlerList::new(),
+            fired: UnsafeCell::new([never; N]),
+        }
+    }
+
+    /// Occupy `registration` with `dispatch`, and split it into `N` [`SharedRegistration`]s,
+    /// where the source of the `n`th registration fired if `fired[n]` returns `true`.
+    ///
+    /// `dispatch` must call [`SharedInterrupt::dispatch`] on `self`. As an occupation can not
+    /// capture any state, [`split_interrupt`] should be used instead, which creates the storage
+    /// of the shared interrupt together with its `dispatch` function.
+    ///
+    /// # Panics
+    /// If this shared interrupt has been split before, or if `registration` panics when it
+    /// is occupied.
+    ///
+    /// [`split_interrupt`]: crate::split_interrupt
+    pub fn split<T, R>(
+        &'static self,
+        registration: R,
+        fired: [fn() -> bool; N],
+        dispatch: fn(),
+    ) -> [SharedRegistration<T, R, N>; N]
+    where
+        T: InterruptNumber,
+        R: NvicInterruptRegistration<T>,
+    {
+        if !self.list.claim() {
+            panic!("Attempted to split a shared interrupt more than once.");
+        }
+
+        // NOTE(unsafe): `dispatch` only reads the predicates after the shared interrupt has been
+        // occupied, which publishes this write, and we are the only ones writing them.
+        unsafe { *self.fired.get() = fired };
+
+        self.list.chain_claimed(registration, dispatch)
+    }
+
+    /// Run the occupation of every source that fired, in order.
+    ///
+    /// This is the occupation of the shared interrupt.
+    pub fn dispatch(&self) {
+        // NOTE(unsafe): the predicates are only written before the shared interrupt is occupied.
+        let fired = unsafe { &*self.fired.get() };
+
+        self.list.dispatch_if(|position| fired[position]());
+    }
+}
+
+impl<const N: usize> Default for SharedInterrupt<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// NOTE(unsafe): the predicates are only written once, before they can be read.
+unsafe impl<const N: usize> Sync for SharedInterrupt<N> {}
+
+fn never() -> bool {
+    false
+}
+
+/// The registration of a single source of a [`SharedInterrupt`].
+///
+/// This is the [`ChainedRegistration`] of the position of the source in the [`HandlerList`] of
+/// the shared interrupt. It has the interrupt number, priority and core of the registration that
+/// it was split from, so it can be passed to drivers that take an [`NvicInterruptRegistration`].
+/// Note that an [`NvicOccupation`] of a [`SharedRegistration`] masks the whole shared interrupt,
+/// and thereby the occupations of all of its sources.
+///
+/// [`NvicOccupation`]: crate::NvicOccupation
+pub type SharedRegistration<T, R, const N: usize> = ChainedRegistration<T, R, N>;
+
+/// Split the [`NvicInterruptRegistration`] `registration` of an interrupt that is shared by
+/// several sources into one [`SharedRegistration`] per source.
+///
+/// Each source is given as a predicate (a `fn() -> bool`) that returns whether it fired. The
+/// registrations are returned as an array, in the same order as the predicates. See
+/// [`SharedInterrupt`] for how the shared interrupt dispatches to the occupations of the
+/// sources.
+///
+/// Every invocation of this macro creates its own `static` [`SharedInterrupt`], so it panics if
+/// it is executed more than once.
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// // For example, using stm32f1xx hal:
+/// use stm32f1xx_hal::pac::{Interrupt, EXTI};
+///
+/// // A line fired if its interrupt is pending, and not masked.
+/// fn fired(line: u8) -> bool {
+///     let exti = unsafe { &*EXTI::ptr() };
+///     exti.pr.read().bits() & exti.imr.read().bits() & (1 << line) != 0
+/// }
+///
+/// fn clear(line: u8) {
+///     let exti = unsafe { &*EXTI::ptr() };
+///     exti.pr.write(|w| unsafe { w.bits(1 << line) });
+/// }
+///
+/// let registration = cortex_m_interrupt::take_nvic_interrupt!(Interrupt::EXTI9_5, 4);
+/// let [line5, line6] = cortex_m_interrupt::split_interrupt!(registration, [|| fired(5), || fired(6)]);
+///
+/// line5.occupy(|| clear(5));
+/// line6.occupy(|| clear(6));
+/// ```
+#[macro_export]
+macro_rules! split_interrupt {
+    ($registration:expr, [$($fired:expr),+ $(,)?]) => {{
+        const N: usize = [$(stringify!($fired)),+].len();
+        static SHARED: $crate::SharedInterrupt<N> = $crate::SharedInterrupt::new();
+        SHARED.split($registration, [$($fired),+], || SHARED.dispatch())
+    }};
+}
+
+#[cfg(all(test, not(loom)))]
+#[test]
+fn shared() {
+    use atomic_polyfill::{AtomicU8, Ordering};
+
+    use crate::{chain::TestRegistration, Handler, InterruptRegistration};
+
+    static PARENT: Handler = Handler::new();
+    static FIRED: AtomicU8 = AtomicU8::new(0);
+    static RAN: AtomicU8 = AtomicU8::new(0);
+
+    let fire = || PARENT.get().unwrap()();
+
+    let registration = TestRegistration::<23> {
+        parent: &PARENT,
+        priority: 3,
+    };
+    let [a, b, c] = crate::split_interrupt!(
+        registration,
+        [
+            || FIRED.load(Ordering::Relaxed) & 0b001 != 0,
+            || FIRED.load(Ordering::Relaxed) & 0b010 != 0,
+            || FIRED.load(Ordering::Relaxed) & 0b100 != 0,
+        ]
+    );
+
+    assert_eq!((a.position(), b.position(), c.position()), (0, 1, 2));
+    assert_eq!(b.number().0, 23);
+    assert_eq!(b.logical_priority().get(), 3);
+
+    a.occupy(|| {
+        RAN.fetch_add(1, Ordering::Relaxed);
+    });
+    c.occupy(|| {
+        RAN.fetch_add(10, Ordering::Relaxed);
+    });
+
+    // Only the occupations of the sources that fired run.
+    FIRED.store(0b001, Ordering::Relaxed);
+    fire();
+    assert_eq!(RAN.load(Ordering::Relaxed), 1);
+
+    // `b` fired, but is not occupied.
+    FIRED.store(0b111, Ordering::Relaxed);
+    fire();
+    assert_eq!(RAN.load(Ordering::Relaxed), 12);
+}
@@ -0,0 +1,188 @@
+use core::{mem::MaybeUninit, ptr};
+
+use crate::sync::{AtomicBool, Ordering, UnsafeCell};
+
+/// A queue that holds at most one value, for passing values between an interrupt and thread mode
+/// (or between two interrupts).
+///
+/// The queue has a single [`Producer`] and a single [`Consumer`], which can be used from different
+/// execution contexts: for instance, an occupation can enqueue a value that thread-mode code
+/// dequeues. Both handles are wait-free and do not use critical sections, so they may be used from
+/// an interrupt of any priority, and either of them may be preempted by the other at any point.
+///
+/// A value that is enqueued by the [`Producer`] is visible to the [`Consumer`] as soon as
+/// [`Producer::enqueue`] returns, and the slot can be reused by the [`Producer`] as soon as
+/// [`Consumer::dequeue`] returns.
+///
+/// The queue can be split in place with [`SingleSlotQueue::split`], or, if it is a `static`, with
+/// [`SingleSlotQueue::try_split`]:
+///
+/// ```
+/// use cortex_m_interrupt::SingleSlotQueue;
+///
+/// static QUEUE: SingleSlotQueue<u32> = SingleSlotQueue::new();
+///
+/// let (mut consumer, mut producer) = QUEUE.try_split().unwrap();
+/// assert!(QUEUE.try_split().is_none());
+///
+/// assert_eq!(producer.enqueue(1), None);
+/// assert_eq!(producer.enqueue(2), Some(2));
+/// assert_eq!(consumer.dequeue(), Some(1));
+/// ```
+pub struct SingleSlotQueue<T> {
+    full: AtomicBool,
+    split: AtomicBool,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> SingleSlotQueue<T> {
+    /// Create a new, empty queue.
+    #[cfg(not(loom))]
+    pub const fn new() -> Self {
+        SingleSlotQueue {
+            full: AtomicBool::new(false),
+            split: AtomicBool::new(false),
+            val: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Create a new, empty queue.
+    #[cfg(loom)]
+    pub fn new() -> Self {
+        SingleSlotQueue {
+            full: AtomicBool::new(false),
+            split: AtomicBool::new(false),
+            val: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Split this queue into a [`Consumer`] and a [`Producer`].
+    pub fn split(&mut self) -> (Consumer<'_, T>, Producer<'_, T>) {
+        (Consumer { ssq: self }, Producer { ssq: self })
+    }
+
+    /// Split this queue into a [`Consumer`] and a [`Producer`], if it has not been split using
+    /// `try_split` before.
+    ///
+    /// Unlike [`SingleSlotQueue::split`], this does not require a mutable reference, so that a
+    /// `static` queue can be split without `unsafe`.
+    pub fn try_split(&self) -> Option<(Consumer<'_, T>, Producer<'_, T>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            Some((Consumer { ssq: self }, Producer { ssq: self }))
+        }
+    }
+}
+
+impl<T> Default for SingleSlotQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SingleSlotQueue<T> {
+    fn drop(&mut self) {
+        if self.full.load(Ordering::Relaxed) {
+            self.val
+                .with_mut(|val| unsafe { ptr::drop_in_place(val as *mut T) });
+        }
+    }
+}
+
+// NOTE(unsafe): the value in the queue is only accessed through the `Consumer` and `Producer`,
+// which are moved to the execution contexts that use them.
+unsafe impl<T: Send> Sync for SingleSlotQueue<T> {}
+
+/// Read handle to a [`SingleSlotQueue`].
+pub struct Consumer<'a, T> {
+    ssq: &'a SingleSlotQueue<T>,
+}
+
+impl<'a, T> Consumer<'a, T> {
+    /// Try reading a value from the queue.
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        if self.ssq.full.load(Ordering::Acquire) {
+            crate::preemption_point();
+            let r = Some(self.ssq.val.with(|val| unsafe { ptr::read(val.cast()) }));
+            crate::preemption_point();
+            self.ssq.full.store(false, Ordering::Release);
+            r
+        } else {
+            None
+        }
+    }
+
+    /// Check if there is a value in the queue.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        !self.ssq.full.load(Ordering::Relaxed)
+    }
+}
+
+// NOTE(unsafe): we guarantee the safety using an `AtomicBool` to gate the read of the `UnsafeCell`.
+unsafe impl<'a, T: Send> Send for Consumer<'a, T> {}
+
+/// Write handle to a [`SingleSlotQueue`].
+pub struct Producer<'a, T> {
+    ssq: &'a SingleSlotQueue<T>,
+}
+
+impl<'a, T> Producer<'a, T> {
+    /// Write a value into the queue. If there is a value already in the queue this will
+    /// return the value given to this method.
+    #[inline]
+    pub fn enqueue(&mut self, val: T) -> Option<T> {
+        if !self.ssq.full.load(Ordering::Acquire) {
+            crate::preemption_point();
+            self.ssq
+                .val
+                .with_mut(|slot| unsafe { ptr::write(slot.cast(), val) });
+            crate::preemption_point();
+            self.ssq.full.store(true, Ordering::Release);
+            None
+        } else {
+            Some(val)
+        }
+    }
+
+    /// Check if there is a value in the queue.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        !self.ssq.full.load(Ordering::Relaxed)
+    }
+}
+
+// NOTE(unsafe): we guarantee the safety using an `AtomicBool` to gate the write of the
+// `UnsafeCell`.
+unsafe impl<'a, T: Send> Send for Producer<'a, T> {}
+
+#[cfg(all(test, not(loom)))]
+#[test]
+fn ssq() {
+    use std::rc::Rc;
+
+    let mut ssq = SingleSlotQueue::new();
+    let value = Rc::new(());
+
+    {
+        let (mut consumer, mut producer) = ssq.split();
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.dequeue(), None);
+
+        assert!(producer.enqueue(value.clone()).is_none());
+        assert!(!producer.is_empty());
+        assert!(producer.enqueue(value.clone()).is_some());
+
+        assert!(consumer.dequeue().is_some());
+        assert!(consumer.is_empty());
+
+        assert!(producer.enqueue(value.clone()).is_none());
+    }
+
+    // The value that is still in the queue is dropped with the queue.
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(ssq);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
@@ -0,0 +1,34 @@
+//! The atomics and cells used by the primitives that are model-checked with `loom`.
+//!
+//! When compiled with `--cfg loom`, these are the atomics and cells of `loom`. Otherwise, they
+//! are the atomics of `atomic-polyfill`, and an [`UnsafeCell`] with the API of the `loom` one.
+
+#[cfg(loom)]
+pub(crate) use loom::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+
+#[cfg(not(loom))]
+pub(crate) use atomic_polyfill::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/// A [`core::cell::UnsafeCell`] with the API of `loom::cell::UnsafeCell`.
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self(core::cell::UnsafeCell::new(value))
+    }
+
+    #[inline(always)]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    #[inline(always)]
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
@@ -0,0 +1,69 @@
+use cortex_m::interrupt::InterruptNumber;
+
+use crate::backend::{Active, Backend};
+
+/// The security state that an interrupt targets on an ARMv8-M core with
+/// the Security Extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetState {
+    /// The interrupt is serviced in Secure state, through the Secure vector table.
+    Secure,
+    /// The interrupt is serviced in Non-secure state, through the Non-secure vector table.
+    NonSecure,
+}
+
+/// Set the security state that `interrupt` targets, by writing to its bit in `NVIC_ITNS`.
+///
+/// # Safety
+/// This function may only be called on an ARMv8-M core with the Security Extension,
+/// while executing in Secure state.
+///
+/// `interrupt` should be masked while its target state is changed.
+pub unsafe fn set_target_state<T: InterruptNumber>(interrupt: T, state: TargetState) {
+    Active::set_target_state(interrupt, state)
+}
+
+/// Get the security state that `interrupt` targets.
+///
+/// # Safety
+/// This function may only be called on an ARMv8-M core with the Security Extension. From
+/// Non-secure state, `NVIC_ITNS` reads as zero, so [`TargetState::Secure`] is always returned.
+pub unsafe fn target_state<T: InterruptNumber>(interrupt: T) -> TargetState {
+    Active::target_state(interrupt)
+}
+
+/// A registration for an interrupt that is routed from the Secure to the Non-secure state.
+///
+/// The proc-macro [`take_nvic_interrupt`] should be used to create an implementor of this
+/// trait, by specifying `target = non_secure`.
+///
+/// The occupation of the interrupt lives in the Non-secure image, which takes the same
+/// interrupt using [`take_nvic_interrupt`] (without a `target`). This registration can be
+/// handed to Secure code that configures a peripheral on behalf of the Non-secure image.
+///
+/// [`take_nvic_interrupt`]: super::take_nvic_interrupt
+pub trait NonSecureRegistration<T: InterruptNumber> {
+    /// The interrupt number that this [`NonSecureRegistration`] is associated with.
+    const INTERRUPT_NUMBER: T;
+
+    /// The interrupt number that this [`NonSecureRegistration`] is associated with.
+    fn number(&self) -> T {
+        Self::INTERRUPT_NUMBER
+    }
+
+    /// The logical priority that this [`NonSecureRegistration`] configures its interrupt with.
+    fn logical_priority(&self) -> core::num::NonZeroU8;
+
+    /// The logical sub-priority that this [`NonSecureRegistration`] configures its interrupt with.
+    fn logical_sub_priority(&self) -> u8 {
+        0
+    }
+
+    /// Route the interrupt of this registration to the Non-secure state.
+    ///
+    /// The interrupt is masked, targeted at the Non-secure state, and its priority
+    /// is configured. It is unmasked once the Non-secure image occupies it.
+    ///
+    /// Calling `route` more than once for the same interrupt will panic.
+    fn route(self);
+}
@@ -0,0 +1,112 @@
+use core::{cell::UnsafeCell, task::Waker};
+
+/// A [`Waker`] that can be registered by a future in thread mode, and woken from an interrupt.
+///
+/// Registering and waking both take the [`Waker`] in a short critical section, so that an
+/// interrupt can never observe a partially registered [`Waker`]. A wake that happens before a
+/// [`Waker`] is registered is lost, so a future should register its [`Waker`] _before_ checking
+/// whether it is ready:
+///
+/// ```rust,ignore
+/// static WAKER: AtomicWaker = AtomicWaker::new();
+///
+/// fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///     WAKER.register(cx.waker());
+///
+///     if self.is_ready() {
+///         Poll::Ready(())
+///     } else {
+///         Poll::Pending
+///     }
+/// }
+///
+/// fn on_interrupt() {
+///     WAKER.wake();
+/// }
+/// ```
+pub struct AtomicWaker {
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+impl AtomicWaker {
+    /// Create a new [`AtomicWaker`], without a registered [`Waker`].
+    pub const fn new() -> Self {
+        Self {
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Register `waker` to be woken by the next call to [`AtomicWaker::wake`].
+    ///
+    /// If a [`Waker`] that wakes the same task is registered already, it is kept.
+    pub fn register(&self, waker: &Waker) {
+        crate::cs::with(|| {
+            // NOTE(unsafe): the waker is only accessed in a critical section.
+            let registered = unsafe { &mut *self.waker.get() };
+
+            match registered {
+                Some(registered) if registered.will_wake(waker) => {}
+                _ => *registered = Some(waker.clone()),
+            }
+        })
+    }
+
+    /// Take the registered [`Waker`], if any.
+    pub fn take(&self) -> Option<Waker> {
+        // NOTE(unsafe): the waker is only accessed in a critical section.
+        crate::cs::with(|| unsafe { &mut *self.waker.get() }.take())
+    }
+
+    /// Wake the registered [`Waker`], if any.
+    ///
+    /// The [`Waker`] is woken outside of the critical section, and is unregistered.
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// NOTE(unsafe): the waker is only accessed in a critical section.
+unsafe impl Sync for AtomicWaker {}
+
+#[cfg(test)]
+#[test]
+fn atomic_waker() {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::Wake,
+    };
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let atomic_waker = AtomicWaker::new();
+
+    // A wake without a registered waker is lost.
+    atomic_waker.wake();
+
+    atomic_waker.register(&waker);
+    atomic_waker.register(&waker);
+    atomic_waker.wake();
+    atomic_waker.wake();
+
+    assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+    assert!(atomic_waker.take().is_none());
+}
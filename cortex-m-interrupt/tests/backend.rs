@@ -0,0 +1,97 @@
+//! Tests of the register accesses performed by registrations, using the simulated backend.
+#![cfg(not(loom))]
+
+use cortex_m_interrupt::{
+    backend::{Active, Backend, Event, Simulated},
+    take_nvic_interrupt, InterruptRegistration, NonSecureRegistration, NvicInterruptRegistration,
+    TargetState,
+};
+
+include!("ui/pac.rs");
+
+const EXTI1: u16 = pac::Interrupt::EXTI1 as u16;
+const EXTI2: u16 = pac::Interrupt::EXTI2 as u16;
+const EXTI3: u16 = pac::Interrupt::EXTI3 as u16;
+
+/// Whether exactly `expected` was recorded. With the `critical-section` feature, critical
+/// sections do not go through the backend, so they are not recorded.
+fn recorded(expected: &[Event]) -> bool {
+    let recorded = |event: &Event| {
+        cfg!(not(feature = "critical-section")) || !matches!(event, Event::Acquire | Event::Release)
+    };
+
+    Simulated::events()
+        .filter(recorded)
+        .eq(expected.iter().copied().filter(recorded))
+}
+
+#[test]
+fn occupy() {
+    Simulated::reset();
+
+    take_nvic_interrupt!(pac::Interrupt::EXTI1, 3).occupy(|| {});
+
+    // The priority bits are determined using the occupied interrupt itself.
+    assert!(recorded(&[
+        Event::Acquire,
+        Event::Mask(EXTI1),
+        Event::SetPriority(EXTI1, 0xFF),
+        Event::SetPriority(EXTI1, 0xD0),
+        Event::Unmask(EXTI1),
+        Event::Release,
+    ]));
+    assert!(Active::is_enabled(pac::Interrupt::EXTI1));
+    assert_eq!(Active::priority(pac::Interrupt::EXTI1), 0xD0);
+
+    Simulated::reset();
+    Active::pend(pac::Interrupt::EXTI2);
+
+    let occupation = take_nvic_interrupt!(
+        pac::Interrupt::EXTI2,
+        priority = 16,
+        prio_bits = pac::NVIC_PRIO_BITS,
+        enable = false,
+        clear_pending = true
+    )
+    .occupy_nvic(|| {});
+
+    assert!(recorded(&[
+        Event::Pend(EXTI2),
+        Event::Acquire,
+        Event::Mask(EXTI2),
+        Event::SetPriority(EXTI2, 0x00),
+        Event::Unpend(EXTI2),
+        Event::Release,
+    ]));
+    assert!(!Active::is_enabled(pac::Interrupt::EXTI2));
+    assert!(!Active::is_pending(pac::Interrupt::EXTI2));
+    assert!(!Simulated::in_critical_section());
+
+    // A masked occupation stays masked after `masked`.
+    occupation.masked(|| {});
+    assert!(!Active::is_enabled(pac::Interrupt::EXTI2));
+
+    // Routing an interrupt to the Non-secure state goes through the backend as well.
+    Simulated::reset();
+
+    take_nvic_interrupt!(
+        pac::Interrupt::EXTI3,
+        2,
+        prio_bits = pac::NVIC_PRIO_BITS,
+        target = non_secure
+    )
+    .route();
+
+    assert!(recorded(&[
+        Event::Acquire,
+        Event::Mask(EXTI3),
+        Event::SetTargetState(EXTI3, TargetState::NonSecure),
+        Event::SetPriority(EXTI3, 0xE0),
+        Event::Release,
+    ]));
+    assert_eq!(
+        unsafe { cortex_m_interrupt::target_state(pac::Interrupt::EXTI3) },
+        TargetState::NonSecure
+    );
+    assert!(!Active::is_enabled(pac::Interrupt::EXTI3));
+}
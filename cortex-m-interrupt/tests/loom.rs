@@ -0,0 +1,207 @@
+//! Model checks of the atomics used to publish occupations, of the [`SingleSlotQueue`] and of the
+//! [`RingBuffer`].
+//!
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --release --test loom`, as the `loom` job in CI does.
+#![cfg(loom)]
+
+use std::cell::Cell;
+
+use cortex_m_interrupt::{Handler, Overflow, RingBuffer, SingleSlotQueue};
+use loom::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+std::thread_local! {
+    static CALLED: Cell<usize> = const { Cell::new(0) };
+}
+
+// Function pointers to the same function are not guaranteed to compare equal, so occupations
+// are identified by calling them.
+fn occupation() {
+    CALLED.with(|called| called.set(1));
+}
+
+fn other_occupation() {
+    CALLED.with(|called| called.set(2));
+}
+
+fn identify(f: Option<fn()>) -> usize {
+    CALLED.with(|called| called.set(0));
+    if let Some(f) = f {
+        f();
+    }
+    CALLED.with(Cell::get)
+}
+
+// In the models below, the interrupt is modelled as a spawned thread. Unlike an interrupt, which
+// runs to completion once it preempted thread mode, a thread can be interleaved with thread mode
+// at every atomic access, so the models cover every point at which the interrupt can fire.
+
+/// An interrupt that fires while its registration is being occupied observes either no
+/// occupation, or the complete occupation together with the state that was written before it.
+#[test]
+fn publish() {
+    loom::model(|| {
+        let handler = Arc::new(Handler::new());
+        let state = Arc::new(UnsafeCell::new(0));
+
+        let occupier = {
+            let handler = handler.clone();
+            let state = state.clone();
+
+            thread::spawn(move || {
+                state.with_mut(|state| unsafe { *state = 42 });
+                assert!(handler.occupy(occupation));
+            })
+        };
+
+        let interrupt = {
+            let handler = handler.clone();
+
+            thread::spawn(move || {
+                let observed = handler.get();
+                if observed.is_some() {
+                    assert_eq!(identify(observed), 1);
+                    state.with(|state| assert_eq!(unsafe { *state }, 42));
+                }
+            })
+        };
+
+        occupier.join().unwrap();
+        interrupt.join().unwrap();
+
+        // Once occupied, the occupation is never stale.
+        assert_eq!(identify(handler.get()), 1);
+    });
+}
+
+/// Of two concurrent occupations, exactly one succeeds, and the interrupt only ever observes
+/// the one that succeeded.
+#[test]
+fn occupy_once() {
+    loom::model(|| {
+        let handler = Arc::new(Handler::new());
+        let succeeded = Arc::new(AtomicUsize::new(0));
+
+        let occupiers: Vec<_> = [(1, occupation as fn()), (2, other_occupation as fn())]
+            .into_iter()
+            .map(|(id, f)| {
+                let handler = handler.clone();
+                let succeeded = succeeded.clone();
+
+                thread::spawn(move || {
+                    if handler.occupy(f) {
+                        succeeded.store(id, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+
+        let interrupt = {
+            let handler = handler.clone();
+            thread::spawn(move || identify(handler.get()))
+        };
+
+        for occupier in occupiers {
+            occupier.join().unwrap();
+        }
+        let observed = interrupt.join().unwrap();
+
+        let succeeded = succeeded.load(Ordering::Relaxed);
+        assert_ne!(succeeded, 0);
+        assert!(observed == 0 || observed == succeeded);
+        assert_eq!(identify(handler.get()), succeeded);
+    });
+}
+
+/// A value that is dequeued is always the complete value that was enqueued, and the slot is
+/// never overwritten before it is dequeued.
+#[test]
+fn single_slot_queue() {
+    loom::model(|| {
+        let ssq: &'static SingleSlotQueue<u32> = Box::leak(Box::new(SingleSlotQueue::new()));
+        let (mut consumer, mut producer) = ssq.try_split().unwrap();
+
+        let producer = thread::spawn(move || {
+            assert!(producer.enqueue(1).is_none());
+            // The second value is only accepted if the first one was dequeued already.
+            let _ = producer.enqueue(2);
+        });
+
+        let consumer = thread::spawn(move || {
+            let first = consumer.dequeue();
+            assert!(matches!(first, None | Some(1)));
+            (first, consumer)
+        });
+
+        producer.join().unwrap();
+        let (first, mut consumer) = consumer.join().unwrap();
+
+        match (first, consumer.dequeue()) {
+            (None, second) => assert_eq!(second, Some(1)),
+            (Some(_), second) => assert!(matches!(second, None | Some(2))),
+        }
+    });
+}
+
+#[test]
+fn ring_buffer() {
+    loom::model(|| {
+        let ring: &'static RingBuffer<u32, 1> =
+            Box::leak(Box::new(RingBuffer::new(Overflow::DropNew)));
+        let (mut consumer, mut producer) = ring.try_split().unwrap();
+
+        let producer = thread::spawn(move || {
+            assert_eq!(producer.enqueue(1), Ok(()));
+            // The second value is only accepted if the first one was dequeued already.
+            let _ = producer.enqueue(2);
+        });
+
+        let consumer = thread::spawn(move || {
+            let first = consumer.dequeue();
+            assert!(matches!(first, None | Some(1)));
+            (first, consumer)
+        });
+
+        producer.join().unwrap();
+        let (first, mut consumer) = consumer.join().unwrap();
+
+        match (first, consumer.dequeue()) {
+            (None, second) => assert_eq!(second, Some(1)),
+            (Some(_), second) => assert!(matches!(second, None | Some(2))),
+        }
+    });
+}
+
+/// With `Overflow::OverwriteOldest`, the producer never overwrites a value while the consumer
+/// reads it, the consumer never dequeues a value twice or out of order, and the newest value is
+/// never lost.
+#[test]
+fn ring_buffer_overwrite() {
+    loom::model(|| {
+        let ring: &'static RingBuffer<u32, 1> =
+            Box::leak(Box::new(RingBuffer::new(Overflow::OverwriteOldest)));
+        let (mut consumer, mut producer) = ring.try_split().unwrap();
+
+        let producer = thread::spawn(move || {
+            assert_eq!(producer.enqueue(1), Ok(()));
+            assert_eq!(producer.enqueue(2), Ok(()));
+        });
+
+        let consumer = thread::spawn(move || (consumer.dequeue(), consumer));
+
+        producer.join().unwrap();
+        let (first, mut consumer) = consumer.join().unwrap();
+
+        match (first, consumer.dequeue()) {
+            (None | Some(1), second) => assert_eq!(second, Some(2)),
+            (first, second) => assert_eq!((first, second), (Some(2), None)),
+        }
+        assert!(ring.len() <= ring.capacity());
+    });
+}
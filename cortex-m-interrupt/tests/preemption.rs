@@ -0,0 +1,340 @@
+//! Tests of the preemption simulator.
+#![cfg(feature = "mock")]
+
+use cortex_m_interrupt::{
+    atomic_polyfill::{AtomicU8, Ordering},
+    backend::{Active, Backend, Simulated},
+    preemption_point, Injection, Overflow, Preemption, Priority, Producer, RingBuffer,
+    RingProducer, SingleSlotQueue,
+};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+include!("ui/pac.rs");
+
+use pac::Interrupt::{EXTI1, EXTI2};
+
+/// The state of the simulated backend, such as whether a critical section is active, is shared by
+/// all tests, so the tests are run one at a time.
+///
+/// The simulated backend is reset, and `EXTI1` and `EXTI2` are unmasked, so that they can be
+/// injected.
+fn serial() -> MutexGuard<'static, ()> {
+    static SERIAL: Mutex<()> = Mutex::new(());
+    let serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+
+    Simulated::reset();
+    // NOTE(unsafe): the tests do not rely on mask-based critical sections, apart from the ones
+    // that they create themselves.
+    unsafe {
+        Active::unmask(EXTI1);
+        Active::unmask(EXTI2);
+    }
+
+    serial
+}
+
+/// A counter that is incremented by thread mode and by an interrupt, each with their own statics.
+struct Counter {
+    count: AtomicU8,
+    fired: AtomicU8,
+}
+
+impl Counter {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU8::new(0),
+            fired: AtomicU8::new(0),
+        }
+    }
+
+    /// A read-modify-write that can be preempted between the read and the write.
+    fn increment(&self) {
+        let value = self.count.load(Ordering::Relaxed);
+        preemption_point();
+        self.count.store(value + 1, Ordering::Relaxed);
+    }
+
+    #[cfg(not(feature = "critical-section"))]
+    fn increment_cs(&self) {
+        Active::free(|| self.increment());
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.fired.store(0, Ordering::Relaxed);
+    }
+
+    fn check(&self) {
+        assert_eq!(
+            self.count.load(Ordering::Relaxed),
+            1 + self.fired.load(Ordering::Relaxed),
+            "lost update"
+        );
+    }
+}
+
+#[test]
+fn lost_update() {
+    static COUNTER: Counter = Counter::new();
+
+    let _serial = serial();
+    let failure = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            COUNTER.fired.fetch_add(1, Ordering::Relaxed);
+            COUNTER.increment();
+        })
+        .explore(|| {
+            COUNTER.reset();
+            COUNTER.increment();
+            COUNTER.check();
+        })
+        .unwrap_err();
+
+    assert_eq!(
+        failure.interleaving,
+        [Injection {
+            interrupt: "A",
+            point: 0
+        }]
+    );
+    assert!(failure.message.contains("lost update"));
+}
+
+// With the `critical-section` feature, critical sections do not go through the simulated backend,
+// so they do not prevent `A` from being injected.
+#[cfg(not(feature = "critical-section"))]
+#[test]
+fn critical_section() {
+    static COUNTER: Counter = Counter::new();
+
+    let _serial = serial();
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            COUNTER.fired.fetch_add(1, Ordering::Relaxed);
+            COUNTER.increment_cs();
+        })
+        .explore(|| {
+            COUNTER.reset();
+            COUNTER.increment_cs();
+            preemption_point();
+            COUNTER.check();
+        });
+
+    // `A` is either injected after the critical section, or not at all.
+    assert_eq!(interleavings, Ok(2));
+}
+
+#[test]
+fn masked() {
+    static COUNTER: Counter = Counter::new();
+
+    let _serial = serial();
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            COUNTER.fired.fetch_add(1, Ordering::Relaxed);
+            COUNTER.increment();
+        })
+        .explore(|| {
+            COUNTER.reset();
+
+            Active::mask(EXTI1);
+            COUNTER.increment();
+            // NOTE(unsafe): this restores the state from before the mask.
+            unsafe { Active::unmask(EXTI1) };
+
+            preemption_point();
+            COUNTER.check();
+        });
+
+    // `A` is either injected after it is unmasked, or not at all.
+    assert_eq!(interleavings, Ok(2));
+}
+
+#[test]
+fn basepri() {
+    let _serial = serial();
+
+    // `BASEPRI` masks `A` (priority 1), but not `B` (priority 2).
+    let basepri = cortex_m_interrupt::logical2hw(Priority::new(1).logical(), 4).unwrap();
+
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            assert_ne!(Active::basepri(), 0, "`A` was injected while masked");
+        })
+        .interrupt("B", EXTI2, Priority::new(2), || {})
+        .explore(|| {
+            Active::set_basepri_max(basepri);
+            preemption_point();
+            // NOTE(unsafe): this restores the state from before `BASEPRI` was raised.
+            unsafe { Active::set_basepri(0) };
+        });
+
+    // Nothing, or `B`.
+    assert_eq!(interleavings, Ok(2));
+}
+
+#[test]
+fn nested() {
+    use cortex_m_interrupt::atomic_polyfill::AtomicBool;
+
+    static B_RUNNING: AtomicBool = AtomicBool::new(false);
+
+    let _serial = serial();
+
+    // `B` may preempt `A`, but not the other way around.
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            assert!(!B_RUNNING.load(Ordering::Relaxed), "`A` preempted `B`");
+            preemption_point();
+        })
+        .interrupt("B", EXTI2, Priority::new(2), || {
+            B_RUNNING.store(true, Ordering::Relaxed);
+            preemption_point();
+            B_RUNNING.store(false, Ordering::Relaxed);
+        })
+        .explore(|| {
+            B_RUNNING.store(false, Ordering::Relaxed);
+            preemption_point();
+        });
+
+    // Nothing, `A`, `A` preempted by `B`, `A` then `B`, `B`, `B` then `A`.
+    assert_eq!(interleavings, Ok(6));
+}
+
+#[test]
+fn single_slot_queue() {
+    static QUEUE: SingleSlotQueue<u8> = SingleSlotQueue::new();
+    static PRODUCER: Mutex<Option<Producer<'static, u8>>> = Mutex::new(None);
+    static ENQUEUED: AtomicU8 = AtomicU8::new(0);
+
+    let _serial = serial();
+    let (mut consumer, producer) = QUEUE.try_split().unwrap();
+    *PRODUCER.lock().unwrap() = Some(producer);
+
+    // An interrupt enqueues two values while thread mode dequeues. Every value that is accepted
+    // by the queue is dequeued exactly once. As the interrupt is injected at most once, and the
+    // last dequeue happens after the last preemption point, the queue is empty after every
+    // interleaving.
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            let mut producer = PRODUCER.lock().unwrap();
+            let producer = producer.as_mut().unwrap();
+
+            for value in [1, 2] {
+                if producer.enqueue(value).is_none() {
+                    ENQUEUED.fetch_add(value, Ordering::Relaxed);
+                }
+            }
+        })
+        .explore(|| {
+            ENQUEUED.store(0, Ordering::Relaxed);
+
+            let mut dequeued = 0;
+            for _ in 0..2 {
+                preemption_point();
+                dequeued += consumer.dequeue().unwrap_or(0);
+            }
+
+            assert_eq!(dequeued, ENQUEUED.load(Ordering::Relaxed));
+        });
+
+    if let Err(failure) = interleavings {
+        panic!("{}", failure);
+    }
+}
+
+#[test]
+fn ring_buffer_overwrite() {
+    static RING: RingBuffer<u8, 2> = RingBuffer::new(Overflow::OverwriteOldest);
+    static PRODUCER: Mutex<Option<RingProducer<'static, u8, 2>>> = Mutex::new(None);
+    static FIRED: AtomicU8 = AtomicU8::new(0);
+
+    let _serial = serial();
+    let (mut consumer, producer) = RING.try_split().unwrap();
+    *PRODUCER.lock().unwrap() = Some(producer);
+
+    // An interrupt enqueues three values into a buffer with room for two, possibly while thread
+    // mode is in the middle of dequeueing the oldest one. Thread mode never sees a value twice or
+    // out of order, and always sees the newest one.
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || {
+            let mut producer = PRODUCER.lock().unwrap();
+            let producer = producer.as_mut().unwrap();
+
+            for value in [1, 2, 3] {
+                assert_eq!(producer.enqueue(value), Ok(()));
+            }
+            FIRED.store(1, Ordering::Relaxed);
+        })
+        .explore(|| {
+            FIRED.store(0, Ordering::Relaxed);
+
+            let mut dequeued = Vec::new();
+            for _ in 0..2 {
+                preemption_point();
+                dequeued.extend(consumer.dequeue());
+            }
+            while let Some(value) = consumer.dequeue() {
+                dequeued.push(value);
+            }
+
+            assert!(dequeued.windows(2).all(|pair| pair[0] < pair[1]));
+            if FIRED.load(Ordering::Relaxed) == 1 {
+                assert_eq!(dequeued.last(), Some(&3));
+            } else {
+                assert!(dequeued.is_empty());
+            }
+        });
+
+    if let Err(failure) = interleavings {
+        panic!("{}", failure);
+    }
+}
+
+#[test]
+fn ring_buffer_overwrite_around() {
+    static RING: RingBuffer<u8, 2> = RingBuffer::new(Overflow::OverwriteOldest);
+    static PRODUCER: Mutex<Option<RingProducer<'static, u8, 2>>> = Mutex::new(None);
+
+    let _serial = serial();
+    let (mut consumer, producer) = RING.try_split().unwrap();
+    *PRODUCER.lock().unwrap() = Some(producer);
+
+    fn enqueue(values: &[u8]) {
+        let mut producer = PRODUCER.lock().unwrap();
+        let producer = producer.as_mut().unwrap();
+
+        for &value in values {
+            assert_eq!(producer.enqueue(value), Ok(()));
+        }
+    }
+
+    // An interrupt enqueues `2 * N` values into a full buffer, which moves the oldest value all
+    // the way around the buffer, possibly while thread mode is in the middle of dequeueing it.
+    // Thread mode must not dequeue the value it read before the interrupt, but the two newest
+    // ones.
+    let interleavings = Preemption::new()
+        .interrupt("A", EXTI1, Priority::new(1), || enqueue(&[3, 4, 5, 6]))
+        .explore(|| {
+            // The interrupt must not preempt the thread-mode producer.
+            Active::free(|| enqueue(&[1, 2]));
+
+            let mut dequeued = Vec::new();
+            preemption_point();
+            dequeued.extend(consumer.dequeue());
+            while let Some(value) = consumer.dequeue() {
+                dequeued.push(value);
+            }
+
+            assert!(
+                dequeued == [1, 2] || dequeued.ends_with(&[5, 6]),
+                "dequeued {:?}",
+                dequeued
+            );
+            assert!(dequeued.windows(2).all(|pair| pair[0] < pair[1]));
+        });
+
+    if let Err(failure) = interleavings {
+        panic!("{}", failure);
+    }
+}
@@ -0,0 +1,89 @@
+//! Tests of the interrupt handlers generated by the macros, which are called directly on the host.
+#![cfg(not(loom))]
+
+use cortex_m_interrupt::{
+    atomic_polyfill::{AtomicU16, AtomicU8, Ordering},
+    cortex_m::peripheral::scb::Exception,
+    take_exception, take_nvic_interrupt, ExceptionRegistration, InterruptRegistration,
+    NvicInterruptRegistration,
+};
+
+include!("ui/pac.rs");
+
+extern "C" {
+    fn EXTI1();
+    fn EXTI2();
+    fn EXTI3();
+    fn SysTick();
+}
+
+/// The core that the test pretends to run on.
+static CORE: AtomicU8 = AtomicU8::new(0);
+
+cortex_m_interrupt::core_id_impl!(|| CORE.load(Ordering::Relaxed));
+
+#[test]
+fn occupy_with_number() {
+    static NUMBER: AtomicU16 = AtomicU16::new(0);
+    static EXCEPTION: AtomicU8 = AtomicU8::new(0);
+
+    fn on_exti(irq: pac::Interrupt) {
+        NUMBER.store(irq as u16, Ordering::Relaxed);
+    }
+
+    fn on_exception(exception: Exception) {
+        EXCEPTION.store((16 + exception.irqn()) as u8, Ordering::Relaxed);
+    }
+
+    let occupation = take_nvic_interrupt!(pac::Interrupt::EXTI3, 2).occupy_with_number(on_exti);
+    assert_eq!(occupation.number(), pac::Interrupt::EXTI3);
+
+    // NOTE(unsafe): the handler is only called from this test.
+    unsafe { EXTI3() };
+    assert_eq!(NUMBER.load(Ordering::Relaxed), pac::Interrupt::EXTI3 as u16);
+
+    take_exception!(SysTick).occupy_with_exception(on_exception);
+
+    // NOTE(unsafe): the handler is only called from this test.
+    unsafe { SysTick() };
+    assert_eq!(EXCEPTION.load(Ordering::Relaxed), 15);
+}
+
+#[test]
+fn per_core() {
+    static RAN: AtomicU8 = AtomicU8::new(0);
+
+    // An interrupt that is taken for one core runs its occupation on that core. On any other
+    // core, it runs the default handler, which never returns, so it is not called from there.
+    CORE.store(1, Ordering::Relaxed);
+    take_nvic_interrupt!(pac::Interrupt::EXTI1, 2, core = 1).occupy(|| {
+        RAN.fetch_add(1, Ordering::Relaxed);
+    });
+
+    // NOTE(unsafe): the handler is only called from this test.
+    unsafe { EXTI1() };
+    assert_eq!(RAN.load(Ordering::Relaxed), 1);
+
+    // An interrupt that is taken for each core runs the occupation of the core that services it.
+    let (core0, core1) = take_nvic_interrupt!(pac::Interrupt::EXTI2, 2, cores = 2);
+    fn core<R: NvicInterruptRegistration<pac::Interrupt>>(_: &R) -> Option<u8> {
+        R::CORE
+    }
+    assert_eq!((core(&core0), core(&core1)), (Some(0), Some(1)));
+
+    CORE.store(0, Ordering::Relaxed);
+    core0.occupy(|| {
+        RAN.fetch_add(10, Ordering::Relaxed);
+    });
+    CORE.store(1, Ordering::Relaxed);
+    core1.occupy(|| {
+        RAN.fetch_add(100, Ordering::Relaxed);
+    });
+
+    for (core, ran) in [(0, 11), (1, 111)] {
+        CORE.store(core, Ordering::Relaxed);
+        // NOTE(unsafe): the handler is only called from this test.
+        unsafe { EXTI2() };
+        assert_eq!(RAN.load(Ordering::Relaxed), ran);
+    }
+}
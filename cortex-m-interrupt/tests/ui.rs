@@ -0,0 +1,11 @@
+//! Compile tests for the macros of `cortex-m-interrupt`.
+//!
+//! The snapshots of the expected errors can be updated by running the tests with `TRYBUILD=overwrite`.
+#![cfg(not(loom))]
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/fail/*.rs");
+    t.pass("tests/ui/pass/*.rs");
+}
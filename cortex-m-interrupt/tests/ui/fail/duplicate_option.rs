@@ -0,0 +1,5 @@
+include!("../pac.rs");
+
+fn main() {
+    let _ = cortex_m_interrupt::take_nvic_interrupt!(pac::Interrupt::EXTI1, priority = 4, ram = true, ram = false);
+}
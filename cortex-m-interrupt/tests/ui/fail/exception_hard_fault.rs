@@ -0,0 +1,3 @@
+fn main() {
+    let _ = cortex_m_interrupt::take_exception!(HardFault);
+}
@@ -0,0 +1,6 @@
+include!("../pac.rs");
+
+#[cortex_m_interrupt::occupy(pac::Interrupt::EXTI1, 4, target = non_secure)]
+fn on_exti1() {}
+
+fn main() {}
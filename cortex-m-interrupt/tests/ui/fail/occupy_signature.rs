@@ -0,0 +1,6 @@
+include!("../pac.rs");
+
+#[cortex_m_interrupt::occupy(pac::Interrupt::EXTI1, 4)]
+fn on_exti1(_value: u32) {}
+
+fn main() {}
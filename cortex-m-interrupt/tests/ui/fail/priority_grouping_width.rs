@@ -0,0 +1,6 @@
+include!("../pac.rs");
+
+fn main() {
+    // Logical priority 3 needs two preemption priority bits, and sub-priority 2 two sub-priority bits.
+    let _ = cortex_m_interrupt::take_nvic_interrupt!(pac::Interrupt::EXTI1, (3, 2), prio_bits = 3);
+}
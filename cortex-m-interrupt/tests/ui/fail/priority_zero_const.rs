@@ -0,0 +1,7 @@
+include!("../pac.rs");
+
+const PRIORITY: u8 = 0;
+
+fn main() {
+    let _ = cortex_m_interrupt::take_nvic_interrupt!(pac::Interrupt::EXTI1, PRIORITY);
+}
@@ -0,0 +1,7 @@
+include!("../pac.rs");
+
+use pac::Interrupt::EXTI1;
+
+fn main() {
+    let _ = cortex_m_interrupt::take_nvic_interrupt!(EXTI1, 4);
+}
@@ -0,0 +1,20 @@
+// A host-side mock of the parts of a PAC that the macros use.
+#[allow(dead_code, non_camel_case_types)]
+mod pac {
+    pub const NVIC_PRIO_BITS: u8 = 4;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[repr(u16)]
+    pub enum Interrupt {
+        EXTI1 = 7,
+        EXTI2 = 8,
+        EXTI3 = 9,
+        EXTI15_10 = 40,
+    }
+
+    unsafe impl cortex_m_interrupt::cortex_m::interrupt::InterruptNumber for Interrupt {
+        fn number(self) -> u16 {
+            self as u16
+        }
+    }
+}
@@ -0,0 +1,7 @@
+use cortex_m_interrupt::{take_exception, ExceptionRegistration, InterruptRegistration};
+
+fn main() {
+    let registration = take_exception!(SysTick);
+    assert_eq!(registration.exception(), cortex_m_interrupt::cortex_m::peripheral::scb::Exception::SysTick);
+    registration.occupy(|| {});
+}
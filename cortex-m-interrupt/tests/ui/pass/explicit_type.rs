@@ -0,0 +1,16 @@
+include!("../pac.rs");
+
+use cortex_m_interrupt::{take_nvic_interrupt, InterruptRegistration, NvicInterruptRegistration};
+use pac::Interrupt::EXTI15_10;
+
+type Irq = pac::Interrupt;
+
+fn take() {
+    let registration = take_nvic_interrupt!(EXTI15_10: Irq, 4);
+    assert_eq!(registration.number(), pac::Interrupt::EXTI15_10);
+    registration.occupy(|| {});
+}
+
+fn main() {
+    let _ = take as fn();
+}
@@ -0,0 +1,30 @@
+include!("../pac.rs");
+
+use cortex_m_interrupt::{take_nvic_interrupt, InterruptRegistration};
+
+cortex_m_interrupt::core_id_impl!(|| 0);
+
+fn take() {
+    let registration = take_nvic_interrupt!(
+        pac::Interrupt::EXTI1,
+        priority = 4,
+        enable = false,
+        clear_pending = true,
+        ram = true,
+    );
+    registration.occupy(|| {});
+
+    let registration = take_nvic_interrupt!(pac::Interrupt::EXTI2, priority = (2, 1), core = 0, target = secure);
+    registration.occupy(|| {});
+
+    let (core0, core1) = take_nvic_interrupt!(pac::Interrupt::EXTI15_10, priority = 3, cores = 2);
+    core0.occupy(|| {});
+    core1.occupy(|| {});
+
+    let registration = take_nvic_interrupt!(pac::Interrupt::EXTI3, 2, target = non_secure);
+    cortex_m_interrupt::NonSecureRegistration::route(registration);
+}
+
+fn main() {
+    let _ = take as fn();
+}
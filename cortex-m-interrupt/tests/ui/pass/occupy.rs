@@ -0,0 +1,9 @@
+include!("../pac.rs");
+
+/// The occupation of `EXTI1`.
+#[cortex_m_interrupt::occupy(pac::Interrupt::EXTI1, priority = 4, enable = false)]
+fn on_exti1() {}
+
+fn main() {
+    let _ = on_exti1 as fn() -> cortex_m_interrupt::NvicOccupation<pac::Interrupt>;
+}
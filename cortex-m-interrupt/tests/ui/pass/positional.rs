@@ -0,0 +1,25 @@
+include!("../pac.rs");
+
+use cortex_m_interrupt::{take_nvic_interrupt, NvicInterruptRegistration, Priority};
+
+const PRIO_BASE: u8 = 2;
+const PRIO_SENSORS: Priority = Priority::new(3);
+
+fn take() {
+    let registration = take_nvic_interrupt!(pac::Interrupt::EXTI1, 4);
+    let _ = registration.occupy_nvic(|| {});
+
+    let registration = take_nvic_interrupt!(pac::Interrupt::EXTI2, (PRIO_BASE + 1, 1));
+    let _ = registration.occupy_nvic(|| {});
+
+    let registration = take_nvic_interrupt!(pac::Interrupt::EXTI3, PRIO_SENSORS, prio_bits = pac::NVIC_PRIO_BITS);
+    let _ = registration.occupy_nvic(|| {});
+
+    // Two preemption priority bits and one sub-priority bit fit in three priority bits.
+    let registration = take_nvic_interrupt!(pac::Interrupt::EXTI15_10, (4, 1), prio_bits = pac::NVIC_PRIO_BITS - 1);
+    let _ = registration.occupy_nvic(|| {});
+}
+
+fn main() {
+    let _ = take as fn();
+}